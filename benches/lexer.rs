@@ -0,0 +1,37 @@
+//! Tokenizing throughput on large generated scripts, as `cargo bench`
+//! reports it. Scripts mix the constructs rc files and generated scripts
+//! are full of: tests, pipelines, quoting, assignments and non-ASCII text.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 10;
+
+fn script(size: usize) -> String {
+    let mut script = String::with_capacity(size + 256);
+    let mut i = 0;
+    while script.len() < size {
+        script.push_str(&format!(
+            "if [ -f \"$HOME/.config/app{n}\" ]; then\n    cat 'file {n}' | grep -v café && echo done >> log; x{n}=1\nfi\n",
+            n = i % 100,
+        ));
+        i += 1;
+    }
+    script
+}
+
+fn main() {
+    for megabytes in [1, 4, 16] {
+        let script = script(megabytes << 20);
+        let mut best = Duration::MAX;
+        let mut tokens = 0;
+        for _ in 0..RUNS {
+            let input = script.clone();
+            let start = Instant::now();
+            tokens = black_box(pearsh::tokenize(input)).len();
+            best = best.min(start.elapsed());
+        }
+        let throughput = script.len() as f64 / best.as_secs_f64() / (1 << 20) as f64;
+        println!("tokenize {:>2} MB: {:>8} tokens in {:>9.2?} ({:.0} MB/s)", megabytes, tokens, best, throughput);
+    }
+}
@@ -0,0 +1,36 @@
+//! The per-command cost of reading, lexing and running input, as
+//! `cargo bench` reports it. Commands go to a handler that does nothing,
+//! so what's timed is the shell's own work on each line.
+
+use std::hint::black_box;
+use std::io;
+use std::time::{Duration, Instant};
+
+use pearsh::shell::{self, Shell};
+
+const COMMANDS: usize = 100_000;
+const RUNS: u32 = 10;
+
+const LINES: &[&str] = &[
+    "x=1 y=\"two words\"",
+    "grep -v 'pattern here' file.txt; sort -u file.txt",
+    "make -C build all && echo done",
+    "git commit -m \"a longer message, as typed at a prompt\"",
+];
+
+fn main() {
+    let mut shell = Shell::default();
+    shell.set_command_handler(|args| {
+        black_box(args);
+        0
+    });
+
+    let mut best = Duration::MAX;
+    for _ in 0..RUNS {
+        let lines = LINES.iter().cycle().take(COMMANDS).map(|line| Ok::<_, io::Error>(line.to_string()));
+        let start = Instant::now();
+        black_box(shell::run_lines(lines, "bench", &mut shell));
+        best = best.min(start.elapsed());
+    }
+    println!("read, lex and run: {:?} per command", best / COMMANDS as u32);
+}
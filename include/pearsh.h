@@ -0,0 +1,33 @@
+/*
+ * C bindings for pearsh's front end. Build the library with
+ *
+ *     cargo rustc --lib --release --features ffi --crate-type cdylib
+ *
+ * (or --crate-type staticlib) and link against the resulting libpearsh.
+ *
+ * Keep in sync with src/ffi.rs.
+ */
+
+#ifndef PEARSH_H
+#define PEARSH_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Tokenizes a NUL-terminated UTF-8 string. Returns a JSON array of tokens,
+ * each like {"kind":"Word","lexeme":"echo","start":0,"end":4}, with byte
+ * offsets into the input. Returns NULL if source is NULL or not UTF-8.
+ * Free the result with pearsh_string_free.
+ */
+char *pearsh_tokenize(const char *source);
+
+/* Frees a string returned by pearsh. Does nothing if string is NULL. */
+void pearsh_string_free(char *string);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* PEARSH_H */
@@ -0,0 +1,97 @@
+use crate::error::PearshError;
+use crate::shell::{ExitStatus, Options, Output, Shell};
+
+/// A command string to run in a fresh shell, set up the way
+/// `std::process::Command` sets up a program:
+///
+/// .   let status = PearshCommand::new("source setup.psh").errexit(true).status()?;
+///
+/// It runs in this process rather than a child, but otherwise as
+/// `pearsh -c` would run it: with `$0` and the positional parameters set
+/// from [`name`] and the arguments, and constructs pearsh can't run
+/// reported before anything runs.
+///
+/// This is not a replacement for `sh -c`. pearsh can't start processes
+/// yet, so only builtins run: a command that names a program on `$PATH`
+/// fails with status 2 instead of running it.
+///
+/// [`name`]: PearshCommand::name
+#[derive(Clone, Debug)]
+pub struct PearshCommand {
+    source: String,
+    name: String,
+    args: Vec<String>,
+    vars: Vec<(String, String)>,
+    options: Options,
+}
+
+impl PearshCommand {
+    pub fn new(source: impl Into<String>) -> PearshCommand {
+        PearshCommand {
+            source: source.into(),
+            name: "pearsh".to_string(),
+            args: Vec::new(),
+            vars: Vec::new(),
+            options: Options::default(),
+        }
+    }
+
+    /// Sets `$0`, which is `pearsh` by default.
+    pub fn name(&mut self, name: impl Into<String>) -> &mut PearshCommand {
+        self.name = name.into();
+        self
+    }
+
+    /// Adds a positional parameter.
+    pub fn arg(&mut self, arg: impl Into<String>) -> &mut PearshCommand {
+        self.args.push(arg.into());
+        self
+    }
+
+    pub fn args<I: IntoIterator<Item = S>, S: Into<String>>(&mut self, args: I) -> &mut PearshCommand {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Sets a shell variable before the command runs.
+    pub fn var(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut PearshCommand {
+        self.vars.push((name.into(), value.into()));
+        self
+    }
+
+    /// Exits as soon as a command fails, as `pearsh -e` does.
+    pub fn errexit(&mut self, on: bool) -> &mut PearshCommand {
+        self.options.errexit = on;
+        self
+    }
+
+    /// Prints each command to stderr before running it, as `pearsh -x`
+    /// does.
+    pub fn xtrace(&mut self, on: bool) -> &mut PearshCommand {
+        self.options.xtrace = on;
+        self
+    }
+
+    /// Runs the command, with its output going to this process's stdout
+    /// and stderr, and returns how it finished. Input that can't be lexed
+    /// is an error, as with [`Shell::eval`]; anything else that goes wrong
+    /// is reported on stderr and shows up in the status.
+    pub fn status(&self) -> Result<ExitStatus, PearshError> {
+        self.shell().eval(&self.source)
+    }
+
+    /// Runs the command, collecting what it writes, as [`Shell::capture`]
+    /// does.
+    pub fn output(&self) -> Result<Output, PearshError> {
+        self.shell().capture(&self.source)
+    }
+
+    fn shell(&self) -> Shell {
+        let mut shell = Shell::new(self.options.clone());
+        shell.set_args(&self.name, &self.args);
+        for (name, value) in &self.vars {
+            shell.set_var(name, value);
+        }
+        shell
+    }
+}
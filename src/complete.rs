@@ -0,0 +1,295 @@
+use std::{env, fs};
+use std::path::{Path, PathBuf};
+
+use crate::lexer;
+use crate::shell::BUILTINS;
+
+/// Characters that need a backslash in an unquoted word.
+const SPECIAL: &str = " \t\\'\"$`&|;<>()[]{}*?!#~";
+
+/// The result of completing the word before the cursor.
+pub struct Completion {
+    /// Start of the word. Everything from here to the cursor is replaced by `text`.
+    pub start: usize,
+    pub text: String,
+    /// Every match, for listing when the completion is ambiguous.
+    pub candidates: Vec<String>,
+}
+
+/// Completes words on the command line.
+pub struct Completer {
+    /// Executables found on `$PATH`, and the value of `$PATH` they came from.
+    commands: Option<(String, Vec<String>)>,
+    /// Recently visited directories, offered when completing `cd`'s argument.
+    recent_dirs: Vec<String>,
+    /// Names of the shell's own variables, offered along with environment
+    /// variables.
+    variables: Vec<String>,
+}
+
+impl Completer {
+    pub fn new() -> Completer {
+        Completer { commands: None, recent_dirs: Vec::new(), variables: Vec::new() }
+    }
+
+    pub fn set_recent_dirs(&mut self, dirs: Vec<String>) {
+        self.recent_dirs = dirs;
+    }
+
+    pub fn set_variables(&mut self, names: Vec<String>) {
+        self.variables = names;
+    }
+
+    /// Completes the word ending at `cursor`.
+    ///
+    /// A name following `$` or `${` is completed against shell and
+    /// environment variables, closing the brace once it is unique. The first
+    /// word of a command is completed against builtins and executables on
+    /// `$PATH`; anything else, or a word containing a `/`, is completed
+    /// against file names, along with recently visited directories for `cd`.
+    /// A unique match is completed in full, followed by a `/` for
+    /// directories or a space otherwise. Several matches complete as far as
+    /// their common prefix. Returns `None` if nothing matches.
+    pub fn complete(&mut self, line: &[char], cursor: usize) -> Option<Completion> {
+        if let Some((start, braced)) = variable_start(line, cursor) {
+            return complete_variable(line, start, cursor, braced, &self.variables);
+        }
+
+        let (start, quote) = word_start(line, cursor);
+        let word = lexer::unquote(&line[start..cursor].iter().collect::<String>());
+        let (dir, prefix) = match word.rfind('/') {
+            Some(i) => word.split_at(i + 1),
+            None => ("", word.as_str()),
+        };
+
+        let mut matches = if dir.is_empty() && is_command_position(line, start) {
+            self.commands()
+                .iter()
+                .filter(|name| name.starts_with(prefix))
+                .map(|name| (name.clone(), false))
+                .collect()
+        } else {
+            files(dir, prefix)
+        };
+        if is_cd_argument(line, start) {
+            matches.extend(
+                self.recent_dirs
+                    .iter()
+                    .filter(|path| path.starts_with(&word))
+                    .map(|path| (path[dir.len()..].to_string(), true)),
+            );
+        }
+        if matches.is_empty() {
+            return None;
+        }
+        matches.sort();
+        matches.dedup();
+
+        Some(completion(start, dir, quote, matches))
+    }
+
+    /// Whether `name` is a builtin or an executable, either on `$PATH` or,
+    /// if it contains a `/`, at that path.
+    pub fn is_command(&mut self, name: &str) -> bool {
+        if name.contains('/') {
+            return is_executable(Path::new(name));
+        }
+        self.commands().iter().any(|command| command == name)
+    }
+
+    /// Builtin names and executables on `$PATH`, rescanning if `$PATH` changed.
+    fn commands(&mut self) -> &[String] {
+        let path = env::var("PATH").unwrap_or_default();
+        if !matches!(&self.commands, Some((cached, _)) if *cached == path) {
+            let mut names: Vec<String> = BUILTINS.iter().map(|name| name.to_string()).collect();
+            for dir in env::split_paths(&path) {
+                let Ok(entries) = fs::read_dir(dir) else {
+                    continue;
+                };
+                names.extend(
+                    entries
+                        .filter_map(Result::ok)
+                        .filter(|entry| is_executable(&entry.path()))
+                        .filter_map(|entry| entry.file_name().into_string().ok()),
+                );
+            }
+            self.commands = Some((path, names));
+        }
+
+        self.commands.as_ref().map_or(&[], |(_, names)| names)
+    }
+}
+
+/// Completes the variable name in `line[start..cursor]`, against the
+/// environment and the shell's own `variables`.
+fn complete_variable(line: &[char], start: usize, cursor: usize, braced: bool, variables: &[String]) -> Option<Completion> {
+    let prefix: String = line[start..cursor].iter().collect();
+    let mut names: Vec<String> = env::vars_os()
+        .filter_map(|(name, _)| name.into_string().ok())
+        .chain(variables.iter().cloned())
+        .filter(|name| name.starts_with(&prefix))
+        .collect();
+    if names.is_empty() {
+        return None;
+    }
+    names.sort();
+    names.dedup();
+
+    let mut text = names.iter().map(String::as_str).reduce(common_prefix).unwrap_or_default().to_string();
+    if braced && names.len() == 1 {
+        text.push('}');
+    }
+
+    Some(Completion { start, text, candidates: names })
+}
+
+/// If the cursor is on a variable name after `$` or `${`, returns where the
+/// name starts and whether it's braced. Single-quoted and escaped dollar
+/// signs don't count, since they aren't expanded.
+fn variable_start(line: &[char], cursor: usize) -> Option<(usize, bool)> {
+    let mut start = cursor;
+    while start > 0 && (line[start - 1].is_alphanumeric() || line[start - 1] == '_') {
+        start -= 1;
+    }
+
+    let braced = start >= 2 && line[start - 2..start] == ['$', '{'];
+    let dollar = if braced { start - 2 } else { start.checked_sub(1)? };
+    if line[dollar] != '$' || (dollar > 0 && line[dollar - 1] == '\\') {
+        return None;
+    }
+    if word_start(line, cursor).1 == Some('\'') {
+        return None;
+    }
+
+    Some((start, braced))
+}
+
+/// Builds the completion for the (sorted) matches of a word in `dir`.
+fn completion(start: usize, dir: &str, quote: Option<char>, matches: Vec<(String, bool)>) -> Completion {
+    let common = matches
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .reduce(common_prefix)
+        .unwrap_or_default();
+    let mut text = requote(&format!("{}{}", dir, common), quote);
+
+    if let [(_, is_dir)] = matches.as_slice() {
+        if *is_dir {
+            text = requote(&format!("{}{}/", dir, common), quote);
+        } else {
+            text.extend(quote);
+            text.push(' ');
+        }
+    }
+
+    let candidates = matches
+        .into_iter()
+        .map(|(name, is_dir)| if is_dir { name + "/" } else { name })
+        .collect();
+
+    Completion { start, text, candidates }
+}
+
+/// Whether a word starting at `start` is the name of a command, i.e. it
+/// begins the line or follows a command separator.
+fn is_command_position(line: &[char], start: usize) -> bool {
+    line[..start]
+        .iter()
+        .rev()
+        .find(|c| !c.is_whitespace())
+        .is_none_or(|c| "|&;(".contains(*c))
+}
+
+/// Whether a word starting at `start` is the first argument of `cd`.
+fn is_cd_argument(line: &[char], start: usize) -> bool {
+    let before: String = line[..start].iter().collect();
+    let before = before.trim_end();
+    before.chars().count() < start
+        && before.strip_suffix("cd").is_some_and(|rest| is_command_position(line, rest.chars().count()))
+}
+
+#[cfg(unix)]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+pub fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Finds where the word ending at `cursor` starts, and the quote character
+/// if the cursor is inside an open quote.
+fn word_start(line: &[char], cursor: usize) -> (usize, Option<char>) {
+    let mut start = 0;
+    let mut quote = None;
+
+    let mut i = 0;
+    while i < cursor {
+        let c = line[i];
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\\' => i += 1,
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c.is_whitespace() || "|&;<>()".contains(c) => start = i + 1,
+            None => {}
+        }
+        i += 1;
+    }
+
+    (start, quote)
+}
+
+/// Quotes a completed word to match how the user started typing it.
+fn requote(word: &str, quote: Option<char>) -> String {
+    match quote {
+        Some('\'') => format!("'{}", word),
+        Some(_) => {
+            let escaped: String = word
+                .chars()
+                .flat_map(|c| if "\"\\$`".contains(c) { vec!['\\', c] } else { vec![c] })
+                .collect();
+            format!("\"{}", escaped)
+        }
+        None => word
+            .chars()
+            .flat_map(|c| if SPECIAL.contains(c) { vec!['\\', c] } else { vec![c] })
+            .collect(),
+    }
+}
+
+/// Lists the entries of `dir` starting with `prefix`, and whether each is a
+/// directory. Hidden entries are only included if `prefix` starts with a dot.
+fn files(dir: &str, prefix: &str) -> Vec<(String, bool)> {
+    let path = match dir.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            PathBuf::from(env::var("HOME").unwrap_or_default() + rest)
+        }
+        _ if dir.is_empty() => PathBuf::from("."),
+        _ => PathBuf::from(dir),
+    };
+
+    let Ok(entries) = fs::read_dir(path) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let hidden = name.starts_with('.') && !prefix.starts_with('.');
+            (name.starts_with(prefix) && !hidden).then(|| (name, entry.path().is_dir()))
+        })
+        .collect()
+}
+
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i);
+    &a[..len]
+}
@@ -0,0 +1,145 @@
+use std::io::{self, IsTerminal};
+use std::ops::Range;
+
+const RESET: &str = "\x1b[0m";
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const YELLOW: &str = "\x1b[1;33m";
+
+/// Error codes, so that each kind of error can be looked up and searched for.
+/// Warnings, from `pearsh --lint`, have codes starting with `W`.
+///
+/// .   E001  unterminated string
+/// .   E002  command not found
+/// .   E003  command not executable
+/// .   E004  unsupported bash construct
+/// .   E005  invalid argument to a registered command
+/// .   E006  value doesn't match a variable's type
+/// .   E007  assignment to a constant
+/// .   E008  malformed list or map literal
+/// .   E009  construct or program pearsh can't run yet
+/// .   W001  unquoted variable expansion
+/// .   W002  useless use of `cat`
+/// .   W003  unreachable code after `exit`
+/// .   W004  variable assigned but never used
+/// .   W005  comparison against `$?`
+pub const UNTERMINATED_STRING: &str = "E001";
+pub const COMMAND_NOT_FOUND: &str = "E002";
+pub const NOT_EXECUTABLE: &str = "E003";
+pub const UNSUPPORTED_BASH: &str = "E004";
+pub const INVALID_ARGUMENT: &str = "E005";
+pub const TYPE_MISMATCH: &str = "E006";
+pub const CONSTANT_ASSIGNMENT: &str = "E007";
+pub const INVALID_LITERAL: &str = "E008";
+pub const UNSUPPORTED_CONSTRUCT: &str = "E009";
+pub const UNQUOTED_EXPANSION: &str = "W001";
+pub const USELESS_CAT: &str = "W002";
+pub const UNREACHABLE: &str = "W003";
+pub const UNUSED_ASSIGNMENT: &str = "W004";
+pub const STATUS_COMPARISON: &str = "W005";
+
+/// Whether a diagnostic stops the input from running.
+#[derive(Clone, Copy, Debug)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error in some input, pointing at the span of it that's to blame.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    /// Byte range of the offending input.
+    pub span: Range<usize>,
+    pub notes: Vec<String>,
+}
+
+/// Where a command came from: the script or other input it was read from,
+/// and the line of that input it starts on.
+#[derive(Clone)]
+pub struct Location {
+    pub name: String,
+    pub line: usize,
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, span: Range<usize>) -> Diagnostic {
+        Diagnostic { severity: Severity::Error, code, message: message.into(), span, notes: Vec::new() }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>, span: Range<usize>) -> Diagnostic {
+        Diagnostic { severity: Severity::Warning, ..Diagnostic::error(code, message, span) }
+    }
+
+    /// Adds a note, shown after the source line.
+    pub fn note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Renders the diagnostic for `source`, the command its span is in.
+    /// `frames` says where that command came from: the file it's in last,
+    /// after the files that sourced it.
+    ///
+    /// The line containing the start of the span is shown with the span
+    /// underlined, followed by the notes and then a line per frame, like so:
+    ///
+    /// .   error[E001]: unterminated string
+    /// .    --> lib.psh:3:6
+    /// .     |
+    /// .   3 | echo "hello
+    /// .     |      ^^^^^^
+    /// .     = note: did you forget a closing quote?
+    /// .     = note: lib.psh was run from script.psh:12
+    ///
+    /// Spans running past the end of that line are underlined to its end.
+    /// Without any frames, lines are numbered from the start of `source`.
+    pub fn render(&self, source: &str, frames: &[Location], colored: bool) -> String {
+        let location = frames.last();
+        let paint = |color: &str, text: &str| if colored { format!("{}{}{}", color, text, RESET) } else { text.to_string() };
+
+        let start = self.span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+        let line = &source[line_start..line_end];
+        let line_number = source[..line_start].matches('\n').count() + location.map_or(1, |loc| loc.line);
+
+        let column = line[..start - line_start].chars().count();
+        let end = self.span.end.clamp(start, line_end);
+        let length = source[start..end].chars().count().max(1);
+
+        let gutter = " ".repeat(line_number.to_string().len());
+        let (label, color) = match self.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+        };
+        let mut rendered = format!("{}: {}\n", paint(color, &format!("{}[{}]", label, self.code)), self.message);
+        if let Some(location) = location {
+            rendered.push_str(&format!("{}{} {}:{}:{}\n", gutter, paint(BLUE, "-->"), location.name, line_number, column + 1));
+        }
+        rendered.push_str(&format!("{} {}\n", gutter, paint(BLUE, "|")));
+        rendered.push_str(&format!("{} {} {}\n", paint(BLUE, &line_number.to_string()), paint(BLUE, "|"), line));
+        rendered.push_str(&format!(
+            "{} {} {}{}\n",
+            gutter,
+            paint(BLUE, "|"),
+            " ".repeat(column),
+            paint(color, &"^".repeat(length))
+        ));
+        let sourced = frames
+            .windows(2)
+            .rev()
+            .map(|pair| format!("{} was run from {}:{}", pair[1].name, pair[0].name, pair[0].line));
+        for note in self.notes.iter().cloned().chain(sourced) {
+            rendered.push_str(&format!("{} {} note: {}\n", gutter, paint(BLUE, "="), note));
+        }
+        rendered
+    }
+
+    /// Prints the diagnostic to stderr, colored if stderr is a terminal.
+    pub fn emit(&self, source: &str, frames: &[Location]) {
+        eprint!("{}", self.render(source, frames, io::stderr().is_terminal()));
+    }
+}
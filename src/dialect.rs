@@ -0,0 +1,214 @@
+use std::ops::Range;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::{self, Token, TokenKind};
+use crate::shell::RESERVED;
+
+/// The language a script is written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dialect {
+    Pearsh,
+    /// Bash, which pearsh runs where the two agree. Bash-only constructs
+    /// are reported up front rather than run as something else.
+    Bash,
+}
+
+impl Dialect {
+    /// Parses a dialect's name, as given to `--dialect=`.
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        match name {
+            "pearsh" => Some(Dialect::Pearsh),
+            "bash" => Some(Dialect::Bash),
+            _ => None,
+        }
+    }
+
+    /// The dialect `source` says it's in: bash if its `#!` line runs bash,
+    /// as in `#!/bin/bash` or `#!/usr/bin/env bash`, and pearsh otherwise.
+    pub fn detect(source: &str) -> Dialect {
+        let interpreter = source
+            .strip_prefix("#!")
+            .and_then(|line| line.lines().next())
+            .and_then(|line| {
+                let mut words = line.split_whitespace();
+                let program = words.next()?;
+                if program.ends_with("/env") { words.next() } else { Some(program) }
+            });
+        match interpreter.map(|program| program.rsplit('/').next().unwrap_or(program)) {
+            Some("bash") => Dialect::Bash,
+            _ => Dialect::Pearsh,
+        }
+    }
+}
+
+/// Bash constructs in `source` that pearsh can't run, with what to write
+/// instead.
+///
+/// These are `[[ ]]` tests, arrays, the `function` keyword, `$'...'`
+/// strings, `&>` redirections, here-strings and process substitution. They
+/// would otherwise lex as something else and quietly do the wrong thing.
+pub fn unsupported_bash(source: &str) -> Vec<Diagnostic> {
+    let stripped = lexer::strip_comments(source);
+    let tokens = lexer::tokenize(stripped.clone());
+
+    let mut found = Vec::new();
+    let mut command_start = true;
+    for (i, token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1).filter(|next| next.span.start == token.span.end);
+        let rest = &stripped[token.span.start..];
+
+        let construct = match token.kind {
+            TokenKind::LSquare if !is_square(previous(&tokens, i)) && rest.starts_with("[[") => Some((
+                "bash's `[[ ]]` test",
+                token.span.start..token.span.start + 2,
+                "use `[ ]` instead, quoting expansions",
+            )),
+            TokenKind::Word if command_start && &*token.lexeme == "function" => Some((
+                "bash's `function` keyword",
+                token.span.clone(),
+                "define functions as `name() { ... }` instead",
+            )),
+            TokenKind::Assign if matches!(next, Some(Token { kind: TokenKind::LParen, .. })) => {
+                Some(("a bash array", token.span.start..token.span.end + 1, "pearsh has no arrays yet"))
+            }
+            TokenKind::Dollar if matches!(next, Some(Token { kind: TokenKind::OneQuoteStr, .. })) => Some((
+                "bash's `$'...'` quoting",
+                token.span.start..next.map_or(token.span.end, |next| next.span.end),
+                "use printf to produce escape sequences instead",
+            )),
+            TokenKind::Ampersand if is_redirect(next) && !is_redirect(previous(&tokens, i)) => Some((
+                "bash's `&>` redirection",
+                token.span.start..next.map_or(token.span.end, |next| next.span.end),
+                "redirect both with `> file 2>&1` instead",
+            )),
+            TokenKind::Unknown if rest.starts_with("<<<") && !stripped[..token.span.start].ends_with('<') => {
+                Some(("a bash here-string", token.span.start..token.span.start + 3, "pipe the string in with printf instead"))
+            }
+            TokenKind::Unknown | TokenKind::Redirect
+                if matches!(&*token.lexeme, "<" | ">")
+                    && matches!(next, Some(Token { kind: TokenKind::LParen, .. })) =>
+            {
+                Some(("bash's process substitution", token.span.start..token.span.end + 1, "use a pipe or a temporary file instead"))
+            }
+            _ => None,
+        };
+        if let Some((what, span, instead)) = construct {
+            found.push(unsupported(what, span, instead));
+        }
+
+        command_start = matches!(
+            token.kind,
+            TokenKind::Newline | TokenKind::Semicolon | TokenKind::Pipe | TokenKind::LogicalAnd | TokenKind::LogicalOr
+        );
+    }
+    found
+}
+
+/// Constructs in `tokens` that pearsh can't run yet in any dialect, with
+/// what to do instead.
+///
+/// These are compound commands, like `if` and `while` and `{ ... }`,
+/// pipelines, redirections and commands run in the background. Running a
+/// command with only some of its parts, or with parts that make no
+/// difference, would quietly do the wrong thing: the body of an `if` would
+/// run whether its test passed or not, and `> file` would never write the
+/// file.
+pub fn unimplemented(source: &str, tokens: &[Token]) -> Vec<Diagnostic> {
+    let mut found = Vec::new();
+    let mut command_start = true;
+    for (i, token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1).filter(|next| next.span.start == token.span.end);
+        let construct = match token.kind {
+            TokenKind::Pipe => Some(("a pipeline".to_string(), token.span.clone(), "pass the output on through a file instead")),
+            TokenKind::Ampersand if !is_redirect(next) && !is_redirect(previous(tokens, i)) => Some((
+                "running a command in the background".to_string(),
+                token.span.clone(),
+                "run it in the foreground instead",
+            )),
+            TokenKind::Redirect | TokenKind::CatRedirect => Some((
+                "redirecting output".to_string(),
+                redirection(tokens, i),
+                "pearsh can't open files for commands yet",
+            )),
+            TokenKind::Unknown if &*token.lexeme == "<" && !is_input(previous(tokens, i)) => Some((
+                "redirecting input".to_string(),
+                redirection(tokens, i),
+                "pearsh can't open files for commands yet",
+            )),
+            _ if command_start => {
+                let span = word_at(tokens, i);
+                let word = &source[span.clone()];
+                RESERVED
+                    .contains(&word)
+                    .then(|| (format!("`{}`", word), span, "pearsh can't run compound commands yet"))
+            }
+            _ => None,
+        };
+        if let Some((what, span, instead)) = construct {
+            found.push(
+                Diagnostic::error(diagnostics::UNSUPPORTED_CONSTRUCT, format!("{} isn't supported yet", what), span)
+                    .note(instead),
+            );
+        }
+
+        command_start = matches!(
+            token.kind,
+            TokenKind::Newline
+                | TokenKind::Semicolon
+                | TokenKind::Pipe
+                | TokenKind::Ampersand
+                | TokenKind::LogicalAnd
+                | TokenKind::LogicalOr
+        );
+    }
+    found
+}
+
+/// The span of the word starting at `tokens[i]`, which takes in the tokens
+/// after it up to the next space.
+fn word_at(tokens: &[Token], i: usize) -> Range<usize> {
+    let mut span = tokens[i].span.clone();
+    for token in &tokens[i + 1..] {
+        if token.span.start != span.end {
+            break;
+        }
+        span.end = token.span.end;
+    }
+    span
+}
+
+/// The span of the redirection operator at `tokens[i]`, along with the
+/// `<`s of a here-document or the `&` of a duplication, as in `2>&1`, that
+/// follow it.
+fn redirection(tokens: &[Token], i: usize) -> Range<usize> {
+    let mut span = tokens[i].span.clone();
+    for token in &tokens[i + 1..] {
+        let part = is_input(Some(token)) || token.kind == TokenKind::Ampersand;
+        if token.span.start != span.end || !part {
+            break;
+        }
+        span.end = token.span.end;
+    }
+    span
+}
+
+fn unsupported(what: &str, span: Range<usize>, instead: &str) -> Diagnostic {
+    Diagnostic::error(diagnostics::UNSUPPORTED_BASH, format!("{} isn't supported", what), span).note(instead)
+}
+
+fn previous(tokens: &[Token], i: usize) -> Option<&Token> {
+    let previous = tokens.get(i.checked_sub(1)?)?;
+    (previous.span.end == tokens[i].span.start).then_some(previous)
+}
+
+fn is_square(token: Option<&Token>) -> bool {
+    matches!(token, Some(Token { kind: TokenKind::LSquare, .. }))
+}
+
+fn is_input(token: Option<&Token>) -> bool {
+    matches!(token, Some(Token { kind: TokenKind::Unknown, lexeme, .. }) if &**lexeme == "<")
+}
+
+fn is_redirect(token: Option<&Token>) -> bool {
+    matches!(token, Some(Token { kind: TokenKind::Redirect | TokenKind::CatRedirect, .. }))
+}
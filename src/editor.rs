@@ -0,0 +1,897 @@
+use std::collections::VecDeque;
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::Once;
+
+use crate::complete::Completer;
+use crate::highlight::highlight;
+use crate::keymap::{Action, Key, Keymap};
+
+/// An interactive line editor, used in place of `Stdin::read_line`.
+///
+/// The terminal is put into raw mode only while a line is being read, so
+/// everything printed between reads behaves as usual. When stdin isn't a
+/// terminal (or raw mode can't be entered), this falls back to a plain
+/// buffered line read.
+pub struct Editor {
+    buffer: Vec<char>,
+    cursor: usize,
+    kill_buffer: Vec<char>,
+    completer: Completer,
+    mode: EditMode,
+    /// In vi mode, whether we're in command mode rather than insert mode.
+    vi_command: bool,
+    emacs_keymap: Keymap,
+    vi_insert_keymap: Keymap,
+    vi_command_keymap: Keymap,
+    /// Terminal width in columns, checked at the start of each line and
+    /// whenever the terminal is resized.
+    width: usize,
+    /// Display columns from the start of the prompt to where the last
+    /// refresh left the cursor, which tells the next one how many rows up
+    /// the line starts.
+    cursor_column: usize,
+    /// Display columns from the start of the prompt to the end of what the
+    /// last refresh drew.
+    end_column: usize,
+}
+
+/// The set of key bindings in use, chosen with `set -o emacs` or `set -o vi`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EditMode {
+    Emacs,
+    Vi,
+}
+
+/// Saves the terminal settings and switches to raw mode; restores them on drop.
+///
+/// This goes through `stty` on the inherited stdin rather than termios
+/// directly, which keeps the crate free of platform-specific bindings.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["raw", "-echo"])?;
+        Ok(RawMode { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Makes a terminal resize (SIGWINCH) interrupt blocking reads, so the
+/// editor wakes up to redraw. The handler itself does nothing; the
+/// interrupted read is the notification.
+///
+/// There's no `stty` equivalent for this, so it calls into the C library
+/// that std already links.
+#[cfg(unix)]
+fn watch_resize() {
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn siginterrupt(signum: i32, flag: i32) -> i32;
+    }
+    extern "C" fn on_resize(_: i32) {}
+    const SIGWINCH: i32 = 28;
+
+    static WATCH: Once = Once::new();
+    WATCH.call_once(|| unsafe {
+        signal(SIGWINCH, on_resize);
+        siginterrupt(SIGWINCH, 1);
+    });
+}
+
+#[cfg(not(unix))]
+fn watch_resize() {}
+
+/// The terminal width in columns, or 80 if it can't be determined.
+fn terminal_width() -> usize {
+    stty(&["size"])
+        .ok()
+        .and_then(|size| size.split_whitespace().nth(1)?.parse().ok())
+        .filter(|&width| width > 0)
+        .unwrap_or(80)
+}
+
+/// Lists `items` in columns, ordered down each column as `ls` does.
+fn print_columns(items: &[String], width: usize, out: &mut impl Write) -> io::Result<()> {
+    let col_width = items.iter().map(|item| display_width(item)).max().unwrap_or(0) + 2;
+    let cols = (width / col_width).max(1);
+    let rows = items.len().div_ceil(cols);
+
+    for row in 0..rows {
+        let line: String = items
+            .iter()
+            .skip(row)
+            .step_by(rows)
+            .map(|item| format!("{}{}", item, " ".repeat(col_width - display_width(item))))
+            .collect();
+        write!(out, "{}\r\n", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// `text` with its newlines shown as `↵`, so that a command entered over
+/// several lines and recalled from the history is drawn on one row.
+fn one_row(text: &str) -> String {
+    text.replace('\n', "↵")
+}
+
+/// How many columns `text` takes up on the terminal. Escape sequences take
+/// none, so colored text measures the same as plain text.
+fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut iter = text.chars();
+    while let Some(c) = iter.next() {
+        match c {
+            // CSI sequences end at a letter; OSC sequences at BEL.
+            '\x1b' => match iter.next() {
+                Some('[') => for _ in iter.by_ref().take_while(|c| !c.is_ascii_alphabetic() && *c != '~') {},
+                Some(']') => for _ in iter.by_ref().take_while(|&c| c != '\x07') {},
+                _ => {}
+            },
+            c => width += char_width(c),
+        }
+    }
+    width
+}
+
+/// How many columns a character takes up: none for control characters and
+/// combining marks, two for wide East Asian characters and emoji, and one
+/// for everything else.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x00..=0x1f | 0x7f..=0x9f => 0,
+        0x0300..=0x036f | 0x1ab0..=0x1aff | 0x1dc0..=0x1dff | 0x20d0..=0x20ff | 0xfe20..=0xfe2f => 0,
+        0x200b..=0x200f | 0xfe00..=0xfe0f => 0,
+        0x1100..=0x115f | 0x2e80..=0x303e | 0x3041..=0x33ff | 0x3400..=0x4dbf | 0x4e00..=0x9fff
+        | 0xa000..=0xa4cf | 0xac00..=0xd7a3 | 0xf900..=0xfaff | 0xfe30..=0xfe4f | 0xff00..=0xff60
+        | 0xffe0..=0xffe6 | 0x1f300..=0x1f64f | 0x1f900..=0x1f9ff | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+/// Vi's word classes: whitespace, word characters and punctuation.
+fn char_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+
+    if !output.status.success() {
+        return Err(io::Error::other("stty failed"));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl Editor {
+    pub fn new() -> Editor {
+        Editor {
+            buffer: Vec::new(),
+            cursor: 0,
+            kill_buffer: Vec::new(),
+            completer: Completer::new(),
+            mode: EditMode::Emacs,
+            vi_command: false,
+            emacs_keymap: Keymap::emacs(),
+            vi_insert_keymap: Keymap::vi_insert(),
+            vi_command_keymap: Keymap::vi_command(),
+            width: 80,
+            cursor_column: 0,
+            end_column: 0,
+        }
+    }
+
+    pub fn mode(&self) -> EditMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: EditMode) {
+        self.mode = mode;
+    }
+
+    /// Shows `prompt` and reads a line of input into `buf`, returning the
+    /// number of bytes read. Up and Down step through `history`.
+    ///
+    /// Mirrors `Stdin::read_line`: the line is appended with its trailing
+    /// newline, and 0 means end of input (Ctrl-D on an empty line).
+    pub fn read_line(&mut self, prompt: &str, history: &[String], buf: &mut String) -> io::Result<usize> {
+        let stdin = io::stdin();
+        let mut out = io::stdout().lock();
+
+        let raw = if stdin.is_terminal() { RawMode::enable().ok() } else { None };
+        let Some(raw) = raw else {
+            write!(out, "{}", prompt)?;
+            out.flush()?;
+            return stdin.read_line(buf);
+        };
+
+        // All but the last line of the prompt is printed once; the last line
+        // is repainted along with the buffer on every redraw.
+        let prompt = match prompt.rsplit_once('\n') {
+            Some((above, last)) => {
+                write!(out, "{}\r\n", above.replace('\n', "\r\n"))?;
+                last
+            }
+            None => prompt,
+        };
+        watch_resize();
+        self.width = terminal_width();
+        self.cursor_column = 0;
+        self.end_column = 0;
+        self.buffer.clear();
+        self.cursor = 0;
+        self.vi_command = false;
+        self.refresh(prompt, None, &mut out)?;
+
+        // Position in history, and the line being typed before moving through it.
+        let mut index = history.len();
+        let mut draft = Vec::new();
+
+        let mut keys = Keys::new(stdin.lock());
+        let mut unhandled = None;
+        let mut last_was_complete = false;
+        let mut suggestion: Option<String> = None;
+        let submitted = loop {
+            let key = match unhandled.take() {
+                Some(key) => key,
+                None => match keys.read_key()? {
+                    Some(key) => key,
+                    None => break !self.buffer.is_empty(),
+                },
+            };
+
+            if key == Key::Resize {
+                self.width = terminal_width();
+                self.refresh(prompt, suggestion.as_deref(), &mut out)?;
+                continue;
+            }
+
+            let action = self.keymap().lookup(key);
+            match action {
+                Some(Action::AcceptLine) => {
+                    self.refresh(prompt, None, &mut out)?;
+                    break true;
+                }
+                Some(Action::DeleteCharOrEof) if self.buffer.is_empty() => break false,
+                Some(Action::Interrupt) => {
+                    self.refresh(prompt, None, &mut out)?;
+                    self.move_to_end(&mut out)?;
+                    write!(out, "^C\r\n")?;
+                    self.buffer.clear();
+                    self.cursor = 0;
+                    (self.cursor_column, self.end_column) = (0, 0);
+                }
+                Some(Action::ClearScreen) => {
+                    write!(out, "\x1b[H\x1b[2J")?;
+                    (self.cursor_column, self.end_column) = (0, 0);
+                }
+                Some(Action::ForwardChar | Action::EndOfLine)
+                    if self.cursor == self.buffer.len() && suggestion.is_some() =>
+                {
+                    self.buffer.extend(suggestion.iter().flat_map(|rest| rest.chars()));
+                    self.cursor = self.buffer.len();
+                }
+                Some(Action::PreviousHistory) if index > 0 => {
+                    if index == history.len() {
+                        draft = self.buffer.clone();
+                    }
+                    index -= 1;
+                    self.set_line(history[index].chars().collect());
+                }
+                Some(Action::NextHistory) if index < history.len() => {
+                    index += 1;
+                    match history.get(index) {
+                        Some(entry) => self.set_line(entry.chars().collect()),
+                        None => self.set_line(draft.clone()),
+                    }
+                }
+                Some(Action::Complete) => self.complete(last_was_complete, &mut out)?,
+                Some(Action::ReverseSearchHistory) => {
+                    unhandled = self.reverse_search(&mut keys, history, &mut out)?;
+                }
+                Some(Action::ViReplaceChar) => {
+                    if let (Some(Key::Char(c)), true) = (keys.read_key()?, self.cursor < self.buffer.len()) {
+                        self.buffer[self.cursor] = c;
+                    }
+                }
+                Some(op @ (Action::ViDelete | Action::ViChange)) => {
+                    let motion = keys.read_key()?.and_then(|key| self.keymap().lookup(key));
+                    self.vi_operator(op, motion);
+                }
+                Some(action) => self.edit(action, key),
+                None => {}
+            }
+
+            // Vi command mode keeps the cursor on a character, never past the end.
+            if self.mode == EditMode::Vi && self.vi_command && self.cursor == self.buffer.len() {
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+
+            suggestion = self.suggest(history);
+            self.refresh(prompt, suggestion.as_deref(), &mut out)?;
+            last_was_complete = action == Some(Action::Complete);
+        };
+
+        self.move_to_end(&mut out)?;
+        write!(out, "\r\n")?;
+        out.flush()?;
+        drop(raw);
+
+        if !submitted {
+            return Ok(0);
+        }
+        let line: String = self.buffer.iter().chain(['\n'].iter()).collect();
+        buf.push_str(&line);
+        Ok(line.len())
+    }
+
+    /// Sets the recently visited directories that completion offers for `cd`.
+    pub fn set_recent_dirs(&mut self, dirs: Vec<String>) {
+        self.completer.set_recent_dirs(dirs);
+    }
+
+    /// Sets the names of shell variables that completion offers after `$`.
+    pub fn set_variables(&mut self, names: Vec<String>) {
+        self.completer.set_variables(names);
+    }
+
+    /// The keymap called `name`: `emacs`, `vi-insert`, or `vi-command` (also
+    /// `vi` or `vi-move`, as in readline). Without a name, the keymap that
+    /// the current editing mode starts each line in.
+    pub fn keymap_mut(&mut self, name: Option<&str>) -> Option<&mut Keymap> {
+        match name {
+            Some("emacs") => Some(&mut self.emacs_keymap),
+            Some("vi-insert") => Some(&mut self.vi_insert_keymap),
+            Some("vi-command" | "vi" | "vi-move") => Some(&mut self.vi_command_keymap),
+            Some(_) => None,
+            None => match self.mode {
+                EditMode::Emacs => Some(&mut self.emacs_keymap),
+                EditMode::Vi => Some(&mut self.vi_insert_keymap),
+            },
+        }
+    }
+
+    /// The keymap for the current editing mode.
+    fn keymap(&self) -> &Keymap {
+        match (self.mode, self.vi_command) {
+            (EditMode::Emacs, _) => &self.emacs_keymap,
+            (EditMode::Vi, false) => &self.vi_insert_keymap,
+            (EditMode::Vi, true) => &self.vi_command_keymap,
+        }
+    }
+
+    /// Runs a Ctrl-R reverse incremental search over `history`.
+    ///
+    /// Typing narrows the search, Ctrl-R steps to older matches, and Esc or
+    /// Ctrl-G cancels, restoring the original line. Any other key accepts
+    /// the match into the buffer and is returned for the caller to handle,
+    /// so Enter accepts and submits in one go.
+    fn reverse_search(
+        &mut self,
+        keys: &mut Keys<impl Read>,
+        history: &[String],
+        out: &mut impl Write,
+    ) -> io::Result<Option<Key>> {
+        let original = (self.buffer.clone(), self.cursor);
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        let mut failed = false;
+
+        let find = |query: &str, before: usize| history[..before].iter().rposition(|entry| entry.contains(query));
+
+        loop {
+            let shown = found.map_or("", |i| history[i].as_str());
+            let label = if failed { "failed reverse-i-search" } else { "reverse-i-search" };
+            let text = format!("({})`{}': {}", label, query, one_row(shown));
+            self.draw(&text, display_width(&text), out)?;
+
+            let Some(key) = keys.read_key()? else {
+                return Ok(None);
+            };
+
+            let before = match key {
+                Key::Resize => {
+                    self.width = terminal_width();
+                    continue;
+                }
+                Key::Char(c) => {
+                    query.push(c);
+                    found.map_or(history.len(), |i| i + 1)
+                }
+                Key::Ctrl('r') => found.unwrap_or(history.len()),
+                Key::Backspace | Key::Ctrl('h') => {
+                    query.pop();
+                    history.len()
+                }
+                Key::Escape | Key::Ctrl('g') => {
+                    (self.buffer, self.cursor) = original;
+                    return Ok(None);
+                }
+                key => {
+                    if let Some(i) = found {
+                        self.set_line(history[i].chars().collect());
+                    }
+                    return Ok(Some(key));
+                }
+            };
+
+            match find(&query, before) {
+                Some(i) => {
+                    found = Some(i);
+                    failed = false;
+                }
+                None => failed = true,
+            }
+        }
+    }
+
+    /// Applies an editing action to the buffer. `key` is the key that
+    /// triggered it, for self-insertion.
+    fn edit(&mut self, action: Action, key: Key) {
+        let len = self.buffer.len();
+        match action {
+            Action::SelfInsert => {
+                if let Key::Char(c) = key {
+                    self.buffer.insert(self.cursor, c);
+                    self.cursor += 1;
+                }
+            }
+            Action::BackwardDeleteChar if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.buffer.remove(self.cursor);
+            }
+            Action::DeleteChar | Action::DeleteCharOrEof if self.cursor < len => {
+                self.buffer.remove(self.cursor);
+            }
+
+            // Motion
+            Action::BackwardChar => self.cursor = self.cursor.saturating_sub(1),
+            Action::ForwardChar => self.cursor = (self.cursor + 1).min(len),
+            Action::BeginningOfLine => self.cursor = 0,
+            Action::EndOfLine => self.cursor = len,
+            Action::BackwardWord => self.cursor = self.word_start(),
+            Action::ForwardWord => self.cursor = self.word_end(),
+
+            // Kill and yank
+            Action::KillLine => self.kill(self.cursor, len),
+            Action::UnixLineDiscard => self.kill(0, self.cursor),
+            Action::UnixWordRubout => {
+                let mut start = self.cursor;
+                while start > 0 && self.buffer[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                while start > 0 && !self.buffer[start - 1].is_whitespace() {
+                    start -= 1;
+                }
+                self.kill(start, self.cursor);
+            }
+            Action::BackwardKillWord => self.kill(self.word_start(), self.cursor),
+            Action::KillWord => self.kill(self.cursor, self.word_end()),
+            Action::Yank => self.put(self.cursor),
+
+            // Vi command mode
+            Action::ViCommandMode => {
+                self.vi_command = true;
+                self.cursor = self.cursor.saturating_sub(1);
+            }
+            Action::ViInsert => self.vi_command = false,
+            Action::ViInsertBeginning => {
+                self.cursor = 0;
+                self.vi_command = false;
+            }
+            Action::ViAppend => {
+                self.cursor = (self.cursor + 1).min(len);
+                self.vi_command = false;
+            }
+            Action::ViAppendEnd => {
+                self.cursor = len;
+                self.vi_command = false;
+            }
+            Action::ViForwardWord => self.cursor = self.vi_word_forward(),
+            Action::ViBackwardWord => self.cursor = self.vi_word_backward(),
+            Action::ViEndWord => self.cursor = self.vi_word_end(),
+            Action::ViFirstNonBlank => {
+                self.cursor = self.buffer.iter().position(|c| !c.is_whitespace()).unwrap_or(len);
+            }
+            Action::ViChangeToEnd => {
+                self.kill(self.cursor, len);
+                self.vi_command = false;
+            }
+            Action::ViChangeLine => {
+                self.kill(0, len);
+                self.vi_command = false;
+            }
+            Action::ViSubstitute => {
+                self.kill(self.cursor, (self.cursor + 1).min(len));
+                self.vi_command = false;
+            }
+            Action::ViPut if !self.kill_buffer.is_empty() => {
+                self.put((self.cursor + 1).min(len));
+                self.cursor -= 1;
+            }
+            Action::ViPutBefore if !self.kill_buffer.is_empty() => {
+                self.put(self.cursor);
+                self.cursor -= 1;
+            }
+            Action::ViToggleCase if self.cursor < len => {
+                let c = self.buffer[self.cursor];
+                let toggled = if c.is_uppercase() { c.to_lowercase().next() } else { c.to_uppercase().next() };
+                self.buffer[self.cursor] = toggled.unwrap_or(c);
+                self.cursor += 1;
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Applies the vi `d` or `c` operator to the text covered by `motion`.
+    /// Doubling the operator (`dd`, `cc`) applies it to the whole line.
+    fn vi_operator(&mut self, op: Action, motion: Option<Action>) {
+        let origin = self.cursor;
+        let (start, end) = match motion {
+            Some(motion) if motion == op => (0, self.buffer.len()),
+
+            // `e` includes the character it lands on, and `cw` acts like `ce`.
+            Some(Action::ViEndWord) => (origin, (self.vi_word_end() + 1).min(self.buffer.len())),
+            Some(Action::ViForwardWord) if op == Action::ViChange => {
+                (origin, (self.vi_word_end() + 1).min(self.buffer.len()))
+            }
+
+            Some(motion @ (Action::BackwardChar | Action::ForwardChar
+                | Action::BeginningOfLine | Action::EndOfLine | Action::ViFirstNonBlank
+                | Action::ViForwardWord | Action::ViBackwardWord)) => {
+                self.edit(motion, Key::Unknown);
+                let target = self.cursor;
+                self.cursor = origin;
+                (origin.min(target), origin.max(target))
+            }
+            _ => return,
+        };
+
+        self.kill(start, end);
+        if op == Action::ViChange {
+            self.vi_command = false;
+        }
+    }
+
+    /// Completes the word before the cursor. If that can't add anything,
+    /// rings the bell, or with `list` set shows the candidates instead.
+    fn complete(&mut self, list: bool, out: &mut impl Write) -> io::Result<()> {
+        let Some(completion) = self.completer.complete(&self.buffer, self.cursor) else {
+            return write!(out, "\x07");
+        };
+
+        let text: Vec<char> = completion.text.chars().collect();
+        if self.buffer[completion.start..self.cursor] != text[..] {
+            self.buffer.splice(completion.start..self.cursor, text.iter().copied());
+            self.cursor = completion.start + text.len();
+            return Ok(());
+        }
+
+        if list && completion.candidates.len() > 1 {
+            self.move_to_end(out)?;
+            write!(out, "\r\n")?;
+            (self.cursor_column, self.end_column) = (0, 0);
+            print_columns(&completion.candidates, self.width, out)
+        } else {
+            write!(out, "\x07")
+        }
+    }
+
+    /// Replaces the buffer, leaving the cursor at the end.
+    fn set_line(&mut self, line: Vec<char>) {
+        self.cursor = line.len();
+        self.buffer = line;
+    }
+
+    /// Removes `start..end` from the buffer into the kill buffer.
+    fn kill(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.kill_buffer = self.buffer.drain(start..end).collect();
+            self.cursor = start;
+        }
+    }
+
+    /// Inserts the kill buffer at `at`, leaving the cursor after it.
+    fn put(&mut self, at: usize) {
+        self.buffer.splice(at..at, self.kill_buffer.iter().copied());
+        self.cursor = at + self.kill_buffer.len();
+    }
+
+    /// Index of the start of the word at or before the cursor.
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && !self.buffer[i - 1].is_alphanumeric() {
+            i -= 1;
+        }
+        while i > 0 && self.buffer[i - 1].is_alphanumeric() {
+            i -= 1;
+        }
+        i
+    }
+
+    /// Index just past the end of the word at or after the cursor.
+    fn word_end(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.buffer.len() && !self.buffer[i].is_alphanumeric() {
+            i += 1;
+        }
+        while i < self.buffer.len() && self.buffer[i].is_alphanumeric() {
+            i += 1;
+        }
+        i
+    }
+
+    /// Index of the start of the next vi word after the cursor.
+    fn vi_word_forward(&self) -> usize {
+        let mut i = self.cursor;
+        if let Some(&c) = self.buffer.get(i) {
+            let class = char_class(c);
+            while i < self.buffer.len() && class != 0 && char_class(self.buffer[i]) == class {
+                i += 1;
+            }
+        }
+        while i < self.buffer.len() && self.buffer[i].is_whitespace() {
+            i += 1;
+        }
+        i
+    }
+
+    /// Index of the start of the vi word before the cursor.
+    fn vi_word_backward(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && self.buffer[i - 1].is_whitespace() {
+            i -= 1;
+        }
+        if i > 0 {
+            let class = char_class(self.buffer[i - 1]);
+            while i > 0 && char_class(self.buffer[i - 1]) == class {
+                i -= 1;
+            }
+        }
+        i
+    }
+
+    /// Index of the last character of the vi word after the cursor.
+    fn vi_word_end(&self) -> usize {
+        let len = self.buffer.len();
+        let mut i = self.cursor + 1;
+        while i < len && self.buffer[i].is_whitespace() {
+            i += 1;
+        }
+        if i < len {
+            let class = char_class(self.buffer[i]);
+            while i + 1 < len && char_class(self.buffer[i + 1]) == class {
+                i += 1;
+            }
+        }
+        i.min(len.saturating_sub(1))
+    }
+
+    /// The rest of the newest history entry that extends the current line,
+    /// if the cursor is at the end of it.
+    fn suggest(&self, history: &[String]) -> Option<String> {
+        if self.buffer.is_empty() || self.cursor < self.buffer.len() {
+            return None;
+        }
+
+        let line: String = self.buffer.iter().collect();
+        history
+            .iter()
+            .rev()
+            .find_map(|entry| entry.strip_prefix(&line).filter(|rest| !rest.is_empty()))
+            .map(str::to_string)
+    }
+
+    /// Redraws the prompt and current line, with `suggestion` dimmed after
+    /// it, and places the cursor.
+    fn refresh(&mut self, prompt: &str, suggestion: Option<&str>, out: &mut impl Write) -> io::Result<()> {
+        let line: String = self.buffer.iter().collect();
+        let line = one_row(&highlight(&line, |name| self.completer.is_command(name)));
+        let suggestion = one_row(suggestion.unwrap_or_default());
+        let indicator = match (self.mode, self.vi_command) {
+            (EditMode::Emacs, _) => "",
+            (EditMode::Vi, false) => "(ins) ",
+            (EditMode::Vi, true) => "(cmd) ",
+        };
+        let text = format!("{}{}{}\x1b[2m{}\x1b[0m", indicator, prompt, line, suggestion);
+
+        let before: String = self.buffer[..self.cursor].iter().collect();
+        let cursor = display_width(indicator) + display_width(prompt) + display_width(&one_row(&before));
+        self.draw(&text, cursor, out)
+    }
+
+    /// Replaces what the last draw left on screen with `text`, which may wrap
+    /// onto several rows, and puts the cursor `cursor` columns into it.
+    fn draw(&mut self, text: &str, cursor: usize, out: &mut impl Write) -> io::Result<()> {
+        let width = self.width.max(1);
+
+        // Back to the row the text starts on, then clear everything below.
+        let up = self.cursor_column / width;
+        if up > 0 {
+            write!(out, "\x1b[{}A", up)?;
+        }
+        write!(out, "\r{}\x1b[J", text)?;
+
+        // A terminal leaves the cursor on the last column after filling a
+        // row, so start the next row explicitly to keep the math simple.
+        let end = display_width(text);
+        if end > 0 && end.is_multiple_of(width) {
+            write!(out, "\r\n")?;
+        }
+        self.cursor_column = end;
+        self.end_column = end;
+        self.move_to(cursor, out)
+    }
+
+    /// Moves the cursor to the end of what the last draw left on screen.
+    fn move_to_end(&mut self, out: &mut impl Write) -> io::Result<()> {
+        self.move_to(self.end_column, out)
+    }
+
+    /// Moves the cursor to `column` columns into the drawn text.
+    fn move_to(&mut self, column: usize, out: &mut impl Write) -> io::Result<()> {
+        let width = self.width.max(1);
+        let (from, to) = (self.cursor_column / width, column / width);
+        if from > to {
+            write!(out, "\x1b[{}A", from - to)?;
+        } else if to > from {
+            write!(out, "\x1b[{}B", to - from)?;
+        }
+
+        write!(out, "\r")?;
+        let offset = column % width;
+        if offset > 0 {
+            write!(out, "\x1b[{}C", offset)?;
+        }
+        self.cursor_column = column;
+        out.flush()
+    }
+}
+
+/// Decodes `bytes` as the input for a single keypress.
+pub fn decode_key(bytes: &[u8]) -> Option<Key> {
+    let mut keys = Keys::new(bytes);
+    let key = keys.read_key().ok().flatten()?;
+    (keys.pending.is_empty() && keys.input.is_empty() && key != Key::Unknown).then_some(key)
+}
+
+/// Decodes keypresses from raw terminal input.
+///
+/// Input is read in whatever chunks the terminal delivers. An escape
+/// sequence always arrives in one chunk, so an ESC with nothing after it
+/// in the same chunk is a press of the Escape key itself.
+struct Keys<R> {
+    input: R,
+    pending: VecDeque<u8>,
+}
+
+impl<R: Read> Keys<R> {
+    fn new(input: R) -> Keys<R> {
+        Keys { input, pending: VecDeque::new() }
+    }
+
+    /// The next byte of input, blocking if none has arrived yet.
+    fn next_byte(&mut self) -> io::Result<Option<u8>> {
+        if self.pending.is_empty() {
+            let mut chunk = [0u8; 64];
+            let n = self.input.read(&mut chunk)?;
+            self.pending.extend(&chunk[..n]);
+        }
+        Ok(self.pending.pop_front())
+    }
+
+    /// The next byte of input, only if it arrived with the previous one.
+    fn pending_byte(&mut self) -> Option<u8> {
+        self.pending.pop_front()
+    }
+
+    /// Reads one keypress. Returns `None` at the end of input, and
+    /// `Key::Resize` if a signal interrupted the wait.
+    fn read_key(&mut self) -> io::Result<Option<Key>> {
+        let byte = match self.next_byte() {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => return Ok(Some(Key::Resize)),
+            byte => byte?,
+        };
+        let Some(byte) = byte else {
+            return Ok(None);
+        };
+
+        let key = match byte {
+            b'\r' | b'\n' => Key::Enter,
+            0x7f => Key::Backspace,
+            0x1b => self.read_escape(),
+            0x01..=0x1a => Key::Ctrl((b'a' + byte - 1) as char),
+            0x00..=0x1f => Key::Unknown,
+            _ => self.read_utf8(byte)?,
+        };
+        Ok(Some(key))
+    }
+
+    /// Decodes the rest of an escape sequence, after the leading ESC.
+    fn read_escape(&mut self) -> Key {
+        match self.pending_byte() {
+            None => Key::Escape,
+
+            // CSI: ESC [ <params> <final byte>
+            Some(b'[') => {
+                let mut params = String::new();
+                let last = loop {
+                    match self.pending_byte() {
+                        Some(b @ 0x30..=0x3f) => params.push(b as char),
+                        Some(b) => break b,
+                        None => return Key::Unknown,
+                    }
+                };
+
+                match (params.as_str(), last) {
+                    ("", b'A') => Key::Up,
+                    ("", b'B') => Key::Down,
+                    ("", b'C') => Key::Right,
+                    ("", b'D') => Key::Left,
+                    ("", b'H') | ("1" | "7", b'~') => Key::Home,
+                    ("", b'F') | ("4" | "8", b'~') => Key::End,
+                    ("3", b'~') => Key::Delete,
+                    ("1;5" | "1;3", b'C') => Key::WordRight,
+                    ("1;5" | "1;3", b'D') => Key::WordLeft,
+                    _ => Key::Unknown,
+                }
+            }
+
+            // SS3: ESC O <final byte>
+            Some(b'O') => match self.pending_byte() {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                _ => Key::Unknown,
+            },
+
+            Some(0x7f) => Key::AltBackspace,
+            Some(b) if b.is_ascii_graphic() => Key::Alt(b as char),
+            _ => Key::Unknown,
+        }
+    }
+
+    /// Decodes a UTF-8 character given its first byte.
+    fn read_utf8(&mut self, first: u8) -> io::Result<Key> {
+        let len = match first {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            0xf0..=0xf7 => 4,
+            _ => 1,
+        };
+
+        let mut bytes = vec![first];
+        for _ in 1..len {
+            match self.next_byte()? {
+                Some(b) => bytes.push(b),
+                None => return Ok(Key::Unknown),
+            }
+        }
+
+        Ok(std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .map_or(Key::Unknown, Key::Char))
+    }
+}
@@ -0,0 +1,128 @@
+use std::{error, fmt, io};
+use std::io::IsTerminal;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use crate::diagnostics::{self, Diagnostic, Location};
+
+/// An error from running shell input.
+///
+/// Variants for parsing and expansion will join these as those stages are
+/// added.
+#[derive(Debug)]
+pub enum PearshError {
+    /// The input couldn't be tokenized.
+    Lex(Diagnostic),
+    /// The input uses something pearsh can't run yet, like a pipeline or a
+    /// program on `$PATH`.
+    Unsupported(Diagnostic),
+    /// No builtin or file on `$PATH` has this name, which is at this span of
+    /// the command.
+    NotFound(String, Range<usize>),
+    /// The command at this span names a file that can't be executed, like a
+    /// directory or a file without execute permission.
+    NotExecutable(PathBuf, Range<usize>),
+    /// A command registered with typed parameters was given the wrong
+    /// number of arguments, or one of the wrong type, at this span.
+    InvalidArgument(String, Range<usize>),
+    /// The value at this span can't be assigned to a variable declared
+    /// with a type, as in `int x = hi`.
+    TypeMismatch(String, Range<usize>),
+    /// The value at this span was assigned to this variable, which was
+    /// declared with `const` and can't be changed.
+    ConstantAssignment(String, Range<usize>),
+    /// The literal given to `let` at this span couldn't be read.
+    InvalidLiteral(String, Range<usize>),
+    Io(io::Error),
+}
+
+impl PearshError {
+    /// The exit status a command failing with this error gets, following
+    /// POSIX: 2 for syntax errors, including malformed literals and constructs
+    /// pearsh can't run yet, and invalid arguments, 127 for a command that
+    /// wasn't found, 126 for one that was found but can't be executed, and 1
+    /// otherwise.
+    pub fn status(&self) -> i32 {
+        match self {
+            PearshError::Lex(_)
+            | PearshError::Unsupported(_)
+            | PearshError::InvalidArgument(..)
+            | PearshError::InvalidLiteral(..) => 2,
+            PearshError::NotFound(..) => 127,
+            PearshError::NotExecutable(..) => 126,
+            PearshError::TypeMismatch(..) | PearshError::ConstantAssignment(..) | PearshError::Io(_) => 1,
+        }
+    }
+
+    /// Renders the error as it's reported. Errors with a span are shown in
+    /// the context of `source`, the command they came from, and `frames`,
+    /// the files it was run from, as [`Diagnostic::render`] shows them.
+    pub fn render(&self, source: &str, frames: &[Location], colored: bool) -> String {
+        let diagnostic = match self {
+            PearshError::Lex(diagnostic) | PearshError::Unsupported(diagnostic) => {
+                return diagnostic.render(source, frames, colored)
+            }
+            PearshError::NotFound(_, span) => {
+                Diagnostic::error(diagnostics::COMMAND_NOT_FOUND, self.to_string(), span.clone())
+            }
+            PearshError::NotExecutable(_, span) => {
+                Diagnostic::error(diagnostics::NOT_EXECUTABLE, self.to_string(), span.clone())
+            }
+            PearshError::InvalidArgument(message, span) => {
+                Diagnostic::error(diagnostics::INVALID_ARGUMENT, message.clone(), span.clone())
+            }
+            PearshError::TypeMismatch(message, span) => {
+                Diagnostic::error(diagnostics::TYPE_MISMATCH, message.clone(), span.clone())
+            }
+            PearshError::InvalidLiteral(message, span) => {
+                Diagnostic::error(diagnostics::INVALID_LITERAL, message.clone(), span.clone())
+            }
+            PearshError::ConstantAssignment(_, span) => {
+                Diagnostic::error(diagnostics::CONSTANT_ASSIGNMENT, self.to_string(), span.clone())
+            }
+            PearshError::Io(err) => return format!("pearsh: {}\n", err),
+        };
+        diagnostic.render(source, frames, colored)
+    }
+
+    /// Prints the error to stderr, rendered as by [`render`](Self::render)
+    /// and colored if stderr is a terminal.
+    pub fn report(&self, source: &str, frames: &[Location]) {
+        eprint!("{}", self.render(source, frames, io::stderr().is_terminal()));
+    }
+}
+
+impl fmt::Display for PearshError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PearshError::Lex(diagnostic) | PearshError::Unsupported(diagnostic) => {
+                write!(f, "error[{}]: {}", diagnostic.code, diagnostic.message)
+            }
+            PearshError::NotFound(name, _) => write!(f, "{}: command not found", name),
+            PearshError::NotExecutable(path, _) if path.is_dir() => write!(f, "{}: is a directory", path.display()),
+            PearshError::NotExecutable(path, _) => write!(f, "{}: permission denied", path.display()),
+            PearshError::InvalidArgument(message, _)
+            | PearshError::TypeMismatch(message, _)
+            | PearshError::InvalidLiteral(message, _) => {
+                write!(f, "{}", message)
+            }
+            PearshError::ConstantAssignment(name, _) => write!(f, "{}: can't assign to a constant", name),
+            PearshError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for PearshError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            PearshError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PearshError {
+    fn from(err: io::Error) -> PearshError {
+        PearshError::Io(err)
+    }
+}
@@ -0,0 +1,56 @@
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+use crate::lexer;
+use crate::value;
+
+/// Tokenizes a NUL-terminated UTF-8 string, returning the tokens as a JSON
+/// array of objects like `{"kind":"Word","lexeme":"echo","start":0,"end":4}`,
+/// where `start` and `end` are byte offsets into the input. Kinds are the
+/// names from `TokenKind::name`.
+///
+/// Returns null if `source` is null or isn't valid UTF-8. The result must
+/// be freed with [`pearsh_string_free`].
+///
+/// # Safety
+///
+/// `source` must be null or point to a NUL-terminated string that stays
+/// valid for the duration of the call.
+#[no_mangle]
+pub unsafe extern "C" fn pearsh_tokenize(source: *const c_char) -> *mut c_char {
+    if source.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(source) = CStr::from_ptr(source).to_str() else {
+        return ptr::null_mut();
+    };
+
+    let tokens: Vec<String> = lexer::tokenize(source.to_string())
+        .iter()
+        .map(|token| {
+            format!(
+                "{{\"kind\":\"{}\",\"lexeme\":{},\"start\":{},\"end\":{}}}",
+                token.kind.name(),
+                value::quote(&token.lexeme),
+                token.span.start,
+                token.span.end
+            )
+        })
+        .collect();
+
+    // The JSON escapes any NULs in lexemes, so this can't fail.
+    CString::new(format!("[{}]", tokens.join(","))).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Frees a string returned by pearsh. Does nothing if `string` is null.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by pearsh that hasn't been
+/// freed already.
+#[no_mangle]
+pub unsafe extern "C" fn pearsh_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
@@ -0,0 +1,233 @@
+use std::ops::Range;
+
+use crate::lexer::{self, Token, TokenKind};
+
+/// Lines longer than this are broken after their `|`, `&&` and `||`.
+const WIDTH: usize = 80;
+
+const INDENT: &str = "    ";
+
+/// A word of a line, or an operator between two commands.
+struct Piece {
+    span: Range<usize>,
+    operator: bool,
+}
+
+/// Formats a script: one space between words and around operators, an
+/// indent per level of nesting, and at most one blank line in a row.
+///
+/// Lines still go where they were, except that lines too long to fit are
+/// broken after their pipes and `&&`/`||` lists, which carry on to the next
+/// line. A line carried on from the one before, after one of those or a
+/// backslash, is indented a level further. Comments are kept, and nothing
+/// inside a word or string changes, so the script means what it did before.
+pub fn format(source: &str) -> String {
+    let comments = lexer::comments(source);
+    let stripped = lexer::strip_comments(source);
+    let tokens = lexer::tokenize(stripped.clone());
+
+    let mut formatted = String::new();
+    let mut depth: usize = 0;
+    let mut continued = false;
+    let mut blank = false;
+    let mut start = 0;
+    for line in tokens.split(|t| matches!(t.kind, TokenKind::Newline)) {
+        let end = line.last().map_or(start, |t| t.span.end);
+        let end = stripped[end..].find('\n').map_or(stripped.len(), |i| end + i);
+        let comment = comments.iter().find(|c| (start..end).contains(&c.start)).map(|c| source[c.clone()].trim_end());
+        start = end + 1;
+
+        let pieces = pieces(line);
+        if pieces.is_empty() && comment.is_none() {
+            blank = !formatted.is_empty();
+            continue;
+        }
+        if blank {
+            formatted.push('\n');
+            blank = false;
+        }
+
+        let words: Vec<&str> = pieces.iter().map(|piece| &stripped[piece.span.clone()]).collect();
+        let level = if continued {
+            depth + 1
+        } else if words.first().is_some_and(|word| is_closer(word)) {
+            depth.max(1) - 1
+        } else {
+            depth
+        };
+        depth = depth.saturating_add_signed(nesting(&pieces, &words));
+        continued = pieces.last().is_some_and(|piece| is_break(&stripped, piece) || stripped[piece.span.clone()].ends_with('\\'));
+
+        let mut lines = render(&stripped, &pieces);
+        if let (Some(last), Some(comment)) = (lines.last_mut(), comment) {
+            if !last.is_empty() {
+                last.push(' ');
+            }
+            last.push_str(comment);
+        }
+        for (i, line) in lines.iter().enumerate() {
+            formatted.push_str(&INDENT.repeat(if i == 0 { level } else { level + 1 }));
+            formatted.push_str(line);
+            formatted.push('\n');
+        }
+    }
+    formatted
+}
+
+/// Groups a line's tokens into pieces: tokens with no space between them
+/// make up one word, except that operators are pieces of their own.
+fn pieces(tokens: &[Token]) -> Vec<Piece> {
+    let mut pieces: Vec<Piece> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let operator = is_operator(tokens, i);
+        match pieces.last_mut() {
+            // `;;`, which ends a `case` pattern's commands
+            Some(last) if operator && last.operator && last.span.end == token.span.start => {
+                last.span.end = token.span.end;
+            }
+            Some(last) if !operator && !last.operator && last.span.end == token.span.start => {
+                last.span.end = token.span.end;
+            }
+            _ => pieces.push(Piece { span: token.span.clone(), operator }),
+        }
+    }
+    pieces
+}
+
+/// Whether `tokens[i]` separates two commands. An `&` next to a `>`, as in
+/// `2>&1`, is part of a redirection instead.
+fn is_operator(tokens: &[Token], i: usize) -> bool {
+    match tokens[i].kind {
+        TokenKind::Semicolon | TokenKind::Pipe | TokenKind::LogicalAnd | TokenKind::LogicalOr => true,
+        TokenKind::Ampersand => {
+            let redirect = |t: &Token| matches!(t.kind, TokenKind::Redirect | TokenKind::CatRedirect);
+            let after = i.checked_sub(1).map(|j| &tokens[j]).is_some_and(|t| redirect(t) && t.span.end == tokens[i].span.start);
+            let before = tokens.get(i + 1).is_some_and(|t| redirect(t) && t.span.start == tokens[i].span.end);
+            !after && !before
+        }
+        _ => false,
+    }
+}
+
+/// Whether a line can be broken after `piece`, leaving the command to carry
+/// on to the next line.
+fn is_break(source: &str, piece: &Piece) -> bool {
+    piece.operator && matches!(&source[piece.span.clone()], "|" | "&&" | "||")
+}
+
+/// Whether a line starting with `word` belongs one level out, since it
+/// ends a block or starts another part of it.
+fn is_closer(word: &str) -> bool {
+    matches!(word, "}" | ")" | "fi" | "done" | "esac" | "then" | "do" | "else" | "elif")
+}
+
+/// How many levels of nesting a line opens, or closes if negative.
+///
+/// Blocks are opened by `{`, `(` or a keyword like `if` starting a command,
+/// unless the block uses braces, and closed by `}`, `)`, `fi`, `done` or
+/// `esac`. Parentheses that aren't words of their own, like `foo()` or a
+/// `case` pattern's `a)`, don't count.
+fn nesting(pieces: &[Piece], words: &[&str]) -> isize {
+    let braced = words.contains(&"{");
+    let mut nesting = 0;
+    for (i, word) in words.iter().enumerate() {
+        let starts_command = i == 0 || pieces[i - 1].operator;
+        match *word {
+            "{" | "(" => nesting += 1,
+            "}" | ")" => nesting -= 1,
+            "if" | "while" | "until" | "for" | "case" | "select" if starts_command && !braced => nesting += 1,
+            "fi" | "done" | "esac" if starts_command => nesting -= 1,
+            _ => {}
+        }
+    }
+    nesting
+}
+
+/// Joins a line's pieces with single spaces, breaking it after each `|`,
+/// `&&` and `||` if it's too long.
+fn render(source: &str, pieces: &[Piece]) -> Vec<String> {
+    let lines = join(source, pieces, false);
+    let width = lines[0].lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    if width > WIDTH {
+        join(source, pieces, true)
+    } else {
+        lines
+    }
+}
+
+/// Joins pieces with single spaces, starting a new line after each `|`,
+/// `&&` and `||` if `wrap` is set. A space escaped with a backslash is kept
+/// as it was.
+fn join(source: &str, pieces: &[Piece], wrap: bool) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for (i, piece) in pieces.iter().enumerate() {
+        let text = &source[piece.span.clone()];
+        let attached = piece.operator && text.starts_with(';');
+        let line = lines.last_mut().expect("there is always a line");
+        if let Some(previous) = i.checked_sub(1).map(|j| &pieces[j]) {
+            if source[previous.span.clone()].ends_with('\\') {
+                line.push_str(&source[previous.span.end..piece.span.start]);
+            } else if !line.is_empty() && !attached {
+                line.push(' ');
+            }
+        }
+        line.push_str(text);
+        if wrap && is_break(source, piece) && i + 1 < pieces.len() {
+            lines.push(String::new());
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCRIPTS: [&str; 6] = [
+        "echo   a  b;echo c&&echo d\n",
+        "if true; then\necho yes # and a comment\nelse\n  echo no\nfi\n",
+        "greet() {\necho 'hello,   world' \"$name  !\"\n}\n\n\n\ngreet\n",
+        "case $x in\na) echo a;;\nesac\n",
+        "ls 2>&1 | grep something-quite-long | sort --reverse --numeric | uniq --count | head -n 10\n",
+        "echo one \\\n  two\n",
+    ];
+
+    #[test]
+    fn spacing_and_indents() {
+        assert_eq!(format("echo   a  b;echo c&&echo d"), "echo a b; echo c && echo d\n");
+        assert_eq!(format("if true; then\necho yes\nfi"), "if true; then\n    echo yes\nfi\n");
+        assert_eq!(format("{\n\n\n\necho a\n}"), "{\n\n    echo a\n}\n");
+    }
+
+    #[test]
+    fn long_lines_are_broken() {
+        assert_eq!(
+            format(SCRIPTS[4]),
+            "ls 2>&1 |\n    grep something-quite-long |\n    sort --reverse --numeric |\n    uniq --count |\n    head -n 10\n"
+        );
+    }
+
+    #[test]
+    fn formatting_twice_changes_nothing() {
+        for script in SCRIPTS {
+            let once = format(script);
+            assert_eq!(format(&once), once, "{:?}", script);
+        }
+    }
+
+    #[test]
+    fn comments_and_strings_are_unchanged() {
+        for script in SCRIPTS {
+            let formatted = format(script);
+            let source = lexer::strip_comments(script);
+            for token in lexer::tokenize(source.clone()) {
+                if matches!(token.kind, TokenKind::OneQuoteStr | TokenKind::TwoQuoteStr) {
+                    assert!(formatted.contains(&source[token.span.clone()]), "{:?}", script);
+                }
+            }
+            for comment in lexer::comments(script) {
+                assert!(formatted.contains(script[comment].trim_end()), "{:?}", script);
+            }
+        }
+    }
+}
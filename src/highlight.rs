@@ -0,0 +1,66 @@
+use crate::lexer::{self, TokenKind};
+
+pub const RESET: &str = "\x1b[0m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+const RED: &str = "\x1b[31m";
+const BOLD: &str = "\x1b[1m";
+
+/// The color for a kind of token: yellow for strings, cyan for operators,
+/// bold for keywords and red for unrecognized characters.
+pub fn color(kind: &TokenKind) -> Option<&'static str> {
+    match kind {
+        TokenKind::OneQuoteStr | TokenKind::TwoQuoteStr => Some(YELLOW),
+        TokenKind::Semicolon | TokenKind::Ampersand | TokenKind::Dollar | TokenKind::Assign
+        | TokenKind::Pipe | TokenKind::Redirect | TokenKind::CatRedirect
+        | TokenKind::Equality | TokenKind::Inequality
+        | TokenKind::LogicalOr | TokenKind::LogicalAnd | TokenKind::LogicalNot
+        | TokenKind::LParen | TokenKind::RParen | TokenKind::LCurly | TokenKind::RCurly
+        | TokenKind::LSquare | TokenKind::RSquare => Some(CYAN),
+        TokenKind::While | TokenKind::For | TokenKind::If | TokenKind::Elif | TokenKind::Else | TokenKind::Const
+        | TokenKind::Let
+        | TokenKind::TypeInt | TokenKind::TypeLong | TokenKind::TypeChar
+        | TokenKind::TypeFloat | TokenKind::TypeDouble | TokenKind::TypeBool => Some(BOLD),
+        TokenKind::Unknown => Some(RED),
+        _ => None,
+    }
+}
+
+/// Colors a line of input by re-lexing it.
+///
+/// Command names that `is_command` accepts are green, and other tokens are
+/// colored as [`color`] says.
+/// Everything between tokens is kept as-is, so the result displays at the
+/// same width as `line`.
+pub fn highlight(line: &str, mut is_command: impl FnMut(&str) -> bool) -> String {
+    let mut highlighted = String::new();
+    let mut end = 0;
+    let mut command_position = true;
+
+    for token in lexer::tokenize(line.to_string()) {
+        let color = match token.kind {
+            TokenKind::Word if command_position && is_command(&token.lexeme) => Some(GREEN),
+            _ => color(&token.kind),
+        };
+
+        highlighted.push_str(&line[end..token.span.start]);
+        match color {
+            Some(color) => highlighted.push_str(&format!("{}{}{}", color, &line[token.span.clone()], RESET)),
+            None => highlighted.push_str(&line[token.span.clone()]),
+        }
+        end = token.span.end;
+
+        // The next word names a command if this token starts or separates commands.
+        command_position = matches!(
+            token.kind,
+            TokenKind::Semicolon | TokenKind::Ampersand | TokenKind::Pipe
+            | TokenKind::LogicalOr | TokenKind::LogicalAnd | TokenKind::LogicalNot
+            | TokenKind::LParen | TokenKind::LCurly | TokenKind::Newline
+            | TokenKind::While | TokenKind::If | TokenKind::Elif | TokenKind::Else
+        );
+    }
+
+    highlighted.push_str(&line[end..]);
+    highlighted
+}
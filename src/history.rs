@@ -0,0 +1,594 @@
+use std::{env, fs, io};
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of entries kept when `$HISTSIZE` isn't set.
+const DEFAULT_HISTSIZE: usize = 500;
+
+/// The first line of a history file pearsh has written, giving the version
+/// of the format, so that a later version can tell what it's reading.
+const HEADER: &str = "#pearsh-history 1";
+
+/// The command history, loaded from and saved to the history file.
+///
+/// The file starts with [`HEADER`], and, as in bash, each entry is preceded
+/// by a `#` line holding the time it was entered, in seconds since the
+/// epoch. An entry runs up to the next time line, so a command entered over
+/// several lines stays one entry. Files without the header, like bash's,
+/// still load, with one entry per line, untimed unless a time line comes
+/// before it.
+pub struct History {
+    entries: Vec<String>,
+    /// When each entry was entered, if known. Parallel to `entries`.
+    times: Vec<Option<u64>>,
+    /// Index of the first entry that hasn't been written to the file yet.
+    unsaved: usize,
+    /// Length of the file when it was last read or written, so that
+    /// [`History::sync`] can tell what other shells have added since.
+    synced: u64,
+    /// Whether the file starts with [`HEADER`], or will once it's written,
+    /// so that its entries can span several lines.
+    versioned: bool,
+    path: Option<PathBuf>,
+    /// How many entries to keep in memory, from `$HISTSIZE`.
+    size: usize,
+    /// How many entries to keep in the file, from `$HISTFILESIZE`.
+    file_size: usize,
+}
+
+impl History {
+    /// Loads the history file at `file`, the value of `$HISTFILE`,
+    /// defaulting to `~/.pearsh_history`. A missing file just means an empty
+    /// history.
+    ///
+    /// All of the file is kept until [`History::set_limits`] says how much
+    /// of it to keep.
+    pub fn load(file: Option<&str>) -> History {
+        let path = file
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".pearsh_history")));
+
+        let contents = path.as_ref().and_then(|path| fs::read_to_string(path).ok()).unwrap_or_default();
+        let versioned = contents.is_empty() || contents.lines().next() == Some(HEADER);
+        let (times, entries): (Vec<_>, Vec<_>) = parse(&contents, versioned).into_iter().unzip();
+
+        History {
+            unsaved: entries.len(),
+            size: entries.len(),
+            file_size: entries.len(),
+            entries,
+            times,
+            synced: contents.len() as u64,
+            versioned,
+            path,
+        }
+    }
+
+    /// Sets how many entries to keep from `size` and `file_size`, the values
+    /// of `$HISTSIZE` and `$HISTFILESIZE`, dropping the oldest entries beyond
+    /// `size`. `size` defaults to 500 and `file_size` to `size`.
+    pub fn set_limits(&mut self, size: Option<&str>, file_size: Option<&str>) {
+        self.size = limit(size).unwrap_or(DEFAULT_HISTSIZE);
+        self.file_size = limit(file_size).unwrap_or(self.size);
+        self.trim();
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Records an entered command, which may span several lines. Blank
+    /// commands aren't recorded, and neither are ones that `control` or
+    /// `ignore`, the values of `$HISTCONTROL` and `$HISTIGNORE`, exclude.
+    ///
+    /// `$HISTCONTROL` is a colon-separated list of `ignorespace` (skip lines
+    /// starting with a space), `ignoredups` (skip repeats of the previous
+    /// line), `ignoreboth` (both), and `erasedups` (remove earlier copies of
+    /// the line). `$HISTIGNORE` is a colon-separated list of glob patterns
+    /// that must match the whole line, where `&` stands for the previous line.
+    pub fn push(&mut self, line: &str, control: &str, ignore: &str) {
+        let line = line.trim_end_matches('\n');
+        if line.trim().is_empty() {
+            return;
+        }
+
+        let control: Vec<&str> = control.split(':').collect();
+        let has = |option: &str| control.contains(&option) || control.contains(&"ignoreboth");
+        let previous = self.entries.last().map(String::as_str);
+        if (has("ignorespace") && line.starts_with(' ')) || (has("ignoredups") && previous == Some(line)) {
+            return;
+        }
+        if ignore
+            .split(':')
+            .filter(|pattern| !pattern.is_empty())
+            .any(|pattern| if pattern == "&" { previous == Some(line) } else { glob_match(pattern, line) })
+        {
+            return;
+        }
+
+        if control.contains(&"erasedups") {
+            let erased = self.entries[..self.unsaved].iter().filter(|entry| *entry == line).count();
+            let mut entries = self.entries.iter();
+            self.times.retain(|_| entries.next().is_some_and(|entry| entry != line));
+            self.entries.retain(|entry| entry != line);
+            self.unsaved -= erased;
+        }
+        self.entries.push(line.to_string());
+        self.times.push(Some(now()));
+        self.trim();
+    }
+
+    /// Drops the oldest entries beyond `$HISTSIZE`.
+    fn trim(&mut self) {
+        let excess = self.entries.len().saturating_sub(self.size);
+        self.entries.drain(..excess);
+        self.times.drain(..excess);
+        self.unsaved = self.unsaved.saturating_sub(excess);
+    }
+
+    /// Appends this session's entries to the history file, then trims the
+    /// file to `$HISTFILESIZE` entries.
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = self.path.clone() else {
+            return Ok(());
+        };
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        file.lock()?;
+        self.append(&mut file)?;
+
+        let entries = parse(&fs::read_to_string(&path)?, self.versioned);
+        let excess = entries.len().saturating_sub(self.file_size);
+        if excess > 0 {
+            let mut contents = format!("{}\n", HEADER);
+            for (time, entry) in &entries[excess..] {
+                contents.push_str(&format!("#{}\n{}\n", time.unwrap_or_else(now), entry));
+            }
+            fs::write(&path, &contents)?;
+            self.synced = contents.len() as u64;
+            self.versioned = true;
+        }
+        Ok(())
+    }
+
+    /// Shares history with other shells using the same file: reads in the
+    /// entries they've written since the last sync, then appends this
+    /// session's new entries. The file is locked while this happens.
+    pub fn sync(&mut self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+        file.lock()?;
+
+        // A shorter file was trimmed by another shell; nothing new can be
+        // told apart from what was already read, so skip ahead.
+        if file.metadata()?.len() > self.synced {
+            let mut contents = String::new();
+            file.seek(SeekFrom::Start(self.synced))?;
+            file.read_to_string(&mut contents)?;
+
+            let (times, entries): (Vec<_>, Vec<_>) = parse(&contents, self.versioned).into_iter().unzip();
+            let count = entries.len();
+            self.entries.splice(self.unsaved..self.unsaved, entries);
+            self.times.splice(self.unsaved..self.unsaved, times);
+            self.unsaved += count;
+        }
+
+        self.append(&mut file)?;
+        self.trim();
+        Ok(())
+    }
+
+    /// Writes the entries that haven't been saved yet to the end of `file`,
+    /// each after its time, or the current time if it has none.
+    fn append(&mut self, file: &mut fs::File) -> io::Result<()> {
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{}", HEADER)?;
+            self.versioned = true;
+        }
+        for (entry, time) in self.entries.iter().zip(&self.times).skip(self.unsaved) {
+            writeln!(file, "#{}\n{}", time.unwrap_or_else(now), entry)?;
+        }
+        self.unsaved = self.entries.len();
+        self.synced = file.metadata()?.len();
+        Ok(())
+    }
+
+    /// Writes the last `count` entries (or all of them) to `out`, numbered,
+    /// as the `history` builtin does. Given `format`, the value of
+    /// `$HISTTIMEFORMAT`, each entry is preceded by its time in that strftime
+    /// format, as [`strftime`] formats it; untimed entries show the current
+    /// time, as in bash.
+    pub fn print(&self, count: Option<usize>, format: Option<&str>, out: &mut dyn Write) -> io::Result<()> {
+        let start = count.map_or(0, |count| self.entries.len().saturating_sub(count));
+        let now = now();
+
+        for (i, entry) in self.entries.iter().enumerate().skip(start) {
+            let time = format.map(|format| strftime(format, self.times[i].unwrap_or(now)));
+            writeln!(out, "{:>5}  {}{}", i + 1, time.unwrap_or_default(), entry)?;
+        }
+        Ok(())
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+/// Splits the history file, or what's been added to it since it was last
+/// read, into entries and their times. Where the file is `versioned`, a
+/// line that doesn't follow a time line carries on the entry before it.
+fn parse(contents: &str, versioned: bool) -> Vec<(Option<u64>, String)> {
+    let mut entries: Vec<(Option<u64>, String)> = Vec::new();
+    let mut time = None;
+    let mut starts_entry = true;
+    let mut lines = contents.lines().peekable();
+    lines.next_if_eq(&HEADER);
+    for line in lines {
+        match line.strip_prefix('#').and_then(|time| time.parse().ok()) {
+            Some(t) => {
+                time = Some(t);
+                starts_entry = true;
+            }
+            None => match entries.last_mut() {
+                Some((_, entry)) if versioned && !starts_entry => {
+                    entry.push('\n');
+                    entry.push_str(line);
+                }
+                _ => {
+                    entries.push((time.take(), line.to_string()));
+                    starts_entry = false;
+                }
+            },
+        }
+    }
+    entries
+}
+
+/// The current time, in seconds since the epoch.
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs())
+}
+
+/// Formats `time`, in seconds since the epoch, as the C library's
+/// `strftime` would with `format`.
+///
+/// Times are shown in UTC, since there's no time zone database to read
+/// local time from, and conversions that depend on the locale are shown as
+/// in the C locale. Conversions that aren't supported, like `%U`, are left
+/// as they are.
+pub(crate) fn strftime(format: &str, time: u64) -> String {
+    const DAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+    const MONTHS: [&str; 12] = [
+        "January", "February", "March", "April", "May", "June", "July", "August", "September", "October", "November",
+        "December",
+    ];
+
+    let date = Date::from_epoch(time);
+    let hour12 = match date.hour % 12 {
+        0 => 12,
+        hour => hour,
+    };
+    let mut formatted = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            formatted.push(c);
+            continue;
+        }
+        let Some(conversion) = chars.next() else {
+            formatted.push('%');
+            break;
+        };
+        let expanded = match conversion {
+            'a' => DAYS[date.weekday][..3].to_string(),
+            'A' => DAYS[date.weekday].to_string(),
+            'b' | 'h' => MONTHS[date.month - 1][..3].to_string(),
+            'B' => MONTHS[date.month - 1].to_string(),
+            'c' => strftime("%a %b %e %H:%M:%S %Y", time),
+            'C' => format!("{:02}", date.year.div_euclid(100)),
+            'd' => format!("{:02}", date.day),
+            'D' => strftime("%m/%d/%y", time),
+            'e' => format!("{:2}", date.day),
+            'F' => strftime("%Y-%m-%d", time),
+            'H' => format!("{:02}", date.hour),
+            'I' => format!("{:02}", hour12),
+            'j' => format!("{:03}", date.yday),
+            'k' => format!("{:2}", date.hour),
+            'l' => format!("{:2}", hour12),
+            'm' => format!("{:02}", date.month),
+            'M' => format!("{:02}", date.minute),
+            'n' => "\n".to_string(),
+            'p' => if date.hour < 12 { "AM" } else { "PM" }.to_string(),
+            'r' => strftime("%I:%M:%S %p", time),
+            'R' => strftime("%H:%M", time),
+            's' => time.to_string(),
+            'S' => format!("{:02}", date.second),
+            't' => "\t".to_string(),
+            'T' => strftime("%H:%M:%S", time),
+            'u' => if date.weekday == 0 { 7 } else { date.weekday }.to_string(),
+            'w' => date.weekday.to_string(),
+            'x' => strftime("%m/%d/%y", time),
+            'X' => strftime("%H:%M:%S", time),
+            'y' => format!("{:02}", date.year.rem_euclid(100)),
+            'Y' => date.year.to_string(),
+            'z' => "+0000".to_string(),
+            'Z' => "UTC".to_string(),
+            '%' => "%".to_string(),
+            _ => format!("%{}", conversion),
+        };
+        formatted.push_str(&expanded);
+    }
+    formatted
+}
+
+/// A time broken down into the fields [`strftime`] shows, in UTC.
+struct Date {
+    year: i64,
+    /// From 1 to 12.
+    month: usize,
+    day: u64,
+    /// The day of the year, from 1.
+    yday: u64,
+    /// From 0, for Sunday, to 6.
+    weekday: usize,
+    hour: u64,
+    minute: u64,
+    second: u64,
+}
+
+impl Date {
+    /// The date `time` seconds after the epoch, using the proleptic
+    /// Gregorian calendar, as in Howard Hinnant's `civil_from_days`.
+    fn from_epoch(time: u64) -> Date {
+        let days = (time / 86400) as i64;
+        let seconds = time % 86400;
+
+        // Days are counted from 0000-03-01, so that the leap day falls at
+        // the end of each year, in eras of 400 years.
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u64;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as usize;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let before: u64 = [31, if leap { 29 } else { 28 }, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31][..month - 1].iter().sum();
+        Date {
+            year,
+            month,
+            day,
+            yday: before + day,
+            // 1970-01-01 was a Thursday.
+            weekday: ((days + 4) % 7) as usize,
+            hour: seconds / 3600,
+            minute: seconds / 60 % 60,
+            second: seconds % 60,
+        }
+    }
+}
+
+fn limit(value: Option<&str>) -> Option<usize> {
+    value?.trim().parse().ok()
+}
+
+/// Whether `pattern` matches all of `text`, where `*` matches any string,
+/// `?` any character, `[...]` any character in the set (or not in it, with
+/// a leading `!` or `^`), and `\\` escapes the next character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    // Where to resume after the last `*`: the pattern position after it, and
+    // the text position it has matched up to so far.
+    let mut star = None;
+    let (mut p, mut t) = (0, 0);
+    while t < text.len() {
+        let matched = match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, t));
+                p += 1;
+                continue;
+            }
+            Some('?') => Some(p + 1),
+            Some('[') => match_class(&pattern, p, text[t]),
+            Some('\\') if p + 1 < pattern.len() => (pattern[p + 1] == text[t]).then_some(p + 2),
+            Some(&c) => (c == text[t]).then_some(p + 1),
+            None => None,
+        };
+
+        match (matched, star) {
+            (Some(next), _) => {
+                p = next;
+                t += 1;
+            }
+            (None, Some((after, matched_to))) => {
+                star = Some((after, matched_to + 1));
+                p = after;
+                t = matched_to + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Matches `c` against the bracket expression starting at `pattern[start]`,
+/// returning the position after it if `c` is in the set. An unterminated
+/// bracket is a literal `[`.
+fn match_class(pattern: &[char], start: usize, c: char) -> Option<usize> {
+    let mut i = start + 1;
+    let negated = matches!(pattern.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut found = false;
+    let mut first = true;
+    while i < pattern.len() && (pattern[i] != ']' || first) {
+        if pattern.get(i + 1) == Some(&'-') && pattern.get(i + 2).is_some_and(|&end| end != ']') {
+            found |= (pattern[i]..=pattern[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            found |= pattern[i] == c;
+            i += 1;
+        }
+        first = false;
+    }
+
+    if i >= pattern.len() {
+        return (c == '[').then_some(start + 1);
+    }
+    (found != negated).then_some(i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    /// An empty history, with no file, that keeps everything it's given.
+    fn history() -> History {
+        History {
+            entries: Vec::new(),
+            times: Vec::new(),
+            unsaved: 0,
+            synced: 0,
+            versioned: true,
+            path: None,
+            size: DEFAULT_HISTSIZE,
+            file_size: DEFAULT_HISTSIZE,
+        }
+    }
+
+    fn pushed(lines: &[&str], control: &str, ignore: &str) -> Vec<String> {
+        let mut history = history();
+        for line in lines {
+            history.push(line, control, ignore);
+        }
+        history.entries
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        assert_eq!(pushed(&["ls\n", "", "  \n", "pwd"], "", ""), ["ls", "pwd"]);
+    }
+
+    #[test]
+    fn histcontrol() {
+        let lines = [" ls", "ls", "ls", "pwd", "ls"];
+        assert_eq!(pushed(&lines, "", ""), lines.map(str::trim_end));
+        assert_eq!(pushed(&lines, "ignorespace", ""), ["ls", "ls", "pwd", "ls"]);
+        assert_eq!(pushed(&lines, "ignoredups", ""), [" ls", "ls", "pwd", "ls"]);
+        assert_eq!(pushed(&lines, "ignoreboth", ""), ["ls", "pwd", "ls"]);
+        assert_eq!(pushed(&lines, "ignorespace:erasedups", ""), ["pwd", "ls"]);
+    }
+
+    #[test]
+    fn histignore() {
+        let lines = ["ls", "ls -l", "cd /tmp", "cd /tmp", "pwd"];
+        assert_eq!(pushed(&lines, "", "ls*:pwd"), ["cd /tmp", "cd /tmp"]);
+        assert_eq!(pushed(&lines, "", "&"), ["ls", "ls -l", "cd /tmp", "pwd"]);
+        assert_eq!(pushed(&lines, "", "cd ?tmp"), ["ls", "ls -l", "pwd"]);
+    }
+
+    #[test]
+    fn glob_matches() {
+        for (pattern, text) in [
+            ("*", ""),
+            ("ls", "ls"),
+            ("l?", "ls"),
+            ("*.rs", "main.rs"),
+            ("a*b*c", "aXbYbc"),
+            ("[abc]x", "bx"),
+            ("[a-c]x", "cx"),
+            ("[!a-c]x", "dx"),
+            ("[^a]", "b"),
+            ("[]]", "]"),
+            ("\\*", "*"),
+            ("[", "["),
+        ] {
+            assert!(glob_match(pattern, text), "{:?} should match {:?}", pattern, text);
+        }
+        for (pattern, text) in [
+            ("ls", "ls -l"),
+            ("l?", "l"),
+            ("*.rs", "main.rc"),
+            ("[abc]x", "dx"),
+            ("[!a-c]x", "bx"),
+            ("\\*", "a"),
+        ] {
+            assert!(!glob_match(pattern, text), "{:?} shouldn't match {:?}", pattern, text);
+        }
+    }
+
+    #[test]
+    fn strftime_conversions() {
+        // 2001-09-09 01:46:40 UTC, a Sunday.
+        let time = 1_000_000_000;
+        for (format, expected) in [
+            ("%Y-%m-%d %H:%M:%S", "2001-09-09 01:46:40"),
+            ("%a %A %b %B %h", "Sun Sunday Sep September Sep"),
+            ("%C %y %j %u %w", "20 01 252 7 0"),
+            ("%e|%k|%l|%I %p", " 9| 1| 1|01 AM"),
+            ("%D %F %T %R", "09/09/01 2001-09-09 01:46:40 01:46"),
+            ("%c", "Sun Sep  9 01:46:40 2001"),
+            ("%s %z %Z", "1000000000 +0000 UTC"),
+            ("100%% %U %", "100% %U %"),
+        ] {
+            assert_eq!(strftime(format, time), expected, "{:?}", format);
+        }
+        assert_eq!(strftime("%c", 0), "Thu Jan  1 00:00:00 1970");
+        assert_eq!(strftime("%F %j", 951_782_400), "2000-02-29 060");
+        assert_eq!(strftime("%r", 951_782_400 + 13 * 3600), "01:00:00 PM");
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let path = env::temp_dir().join(format!("pearsh-history-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        let file = path.to_str().expect("the temporary directory is UTF-8");
+
+        let mut history = History::load(Some(file));
+        history.set_limits(None, None);
+        for line in ["ls", "echo '#1'", "if true\nthen\n    pwd\nfi\n", "pwd"] {
+            history.push(line, "", "");
+        }
+        history.save().expect("the history file can be written");
+
+        let contents = fs::read_to_string(&path).expect("the history file can be read");
+        assert!(contents.starts_with(&format!("{}\n", HEADER)), "{:?}", contents);
+        let loaded = History::load(Some(file));
+        assert_eq!(loaded.entries, ["ls", "echo '#1'", "if true\nthen\n    pwd\nfi", "pwd"]);
+        assert_eq!(loaded.times, history.times);
+
+        // Trimming the file rewrites it, keeping the entries whole.
+        let mut history = loaded;
+        history.set_limits(None, Some("2"));
+        history.push("exit", "", "");
+        history.save().expect("the history file can be written");
+        let loaded = History::load(Some(file));
+        assert_eq!(loaded.entries, ["pwd", "exit"]);
+        history.set_limits(None, Some("3"));
+        history.push("for x in a b\ndo echo $x\ndone", "", "");
+        history.save().expect("the history file can be written");
+        let loaded = History::load(Some(file));
+        assert_eq!(loaded.entries, ["pwd", "exit", "for x in a b\ndo echo $x\ndone"]);
+
+        // A file written by bash, without the header or times, loads too.
+        fs::write(&path, "ls\n#1700000000\npwd\ncd\n").expect("the history file can be written");
+        let loaded = History::load(Some(file));
+        assert_eq!(loaded.entries, ["ls", "pwd", "cd"]);
+        assert_eq!(loaded.times, [None, Some(1_700_000_000), None]);
+        let _ = fs::remove_file(&path);
+    }
+}
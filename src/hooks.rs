@@ -0,0 +1,77 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::PearshError;
+
+/// Callbacks into a shell as it runs, for prompt frameworks, telemetry and
+/// other tools built on pearsh. Register one with [`Shell::add_hook`].
+/// Every method does nothing unless overridden.
+///
+/// [`Shell::add_hook`]: crate::Shell::add_hook
+pub trait ShellHook {
+    /// Called before each command runs, with the command as written.
+    fn on_command_start(&mut self, _command: &str) {}
+
+    /// Called after each command runs, with its status and how long it
+    /// took. Not called for `exit`, which ends the shell instead.
+    fn on_command_end(&mut self, _command: &str, _status: i32, _elapsed: Duration) {}
+
+    /// Called before the interactive shell shows each prompt.
+    fn on_prompt(&mut self) {}
+
+    /// Called after `cd` changes the working directory.
+    fn on_cd(&mut self, _from: &Path, _to: &Path) {}
+
+    /// Called for each error the shell reports as a diagnostic, like a
+    /// command that isn't found or a construct pearsh can't run, before
+    /// it's printed. Builtins' usage messages, like `cd: too many
+    /// arguments`, are only printed.
+    fn on_error(&mut self, _error: &PearshError) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::dialect::Dialect;
+    use crate::shell::{Options, Shell};
+
+    /// Keeps the message of each error it's called with.
+    struct Errors(Rc<RefCell<Vec<String>>>);
+
+    impl ShellHook for Errors {
+        fn on_error(&mut self, error: &PearshError) {
+            self.0.borrow_mut().push(error.to_string());
+        }
+    }
+
+    fn errors(options: Options, source: &str) -> Vec<String> {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let mut shell = Shell::new(options);
+        shell.add_hook(Errors(errors.clone()));
+        shell.capture(source).expect("the source lexes");
+        errors.take()
+    }
+
+    #[test]
+    fn on_error_sees_commands_that_fail() {
+        assert_eq!(errors(Options::default(), "no-such-command-anywhere"), ["no-such-command-anywhere: command not found"]);
+    }
+
+    #[test]
+    fn on_error_sees_constructs_that_cant_run() {
+        let errors = errors(Options::default(), "true | true\nwhile true");
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|error| error.starts_with("error[E009]")), "{:?}", errors);
+    }
+
+    #[test]
+    fn on_error_sees_bash_constructs() {
+        let options = Options { dialect: Some(Dialect::Bash), ..Options::default() };
+        let errors = errors(options, "x=(1 2)");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("error[E"), "{:?}", errors);
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+
+/// A decoded keypress.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+    Alt(char),
+    Enter,
+    Escape,
+    Backspace,
+    AltBackspace,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    WordLeft,
+    WordRight,
+    Unknown,
+    /// Not a keypress: the terminal was resized while waiting for one.
+    Resize,
+}
+
+impl Key {
+    /// Parses a readline-style key sequence, as `bind` takes them: `\C-x`
+    /// for Ctrl, `\M-x` or `\ex` for Alt, `\e[A` and friends for the
+    /// special keys, and plain characters. The sequence must make up exactly
+    /// one key.
+    pub fn parse(seq: &str) -> Option<Key> {
+        let mut bytes = Vec::new();
+        let mut iter = seq.chars();
+        while let Some(c) = iter.next() {
+            if c != '\\' {
+                bytes.extend(c.to_string().bytes());
+                continue;
+            }
+
+            match iter.next()? {
+                'C' | 'M' if iter.clone().next() != Some('-') => return None,
+                'C' => {
+                    iter.next();
+                    match iter.next()? {
+                        '?' => bytes.push(0x7f),
+                        c if c.is_ascii_alphabetic() || "@[\\]^_".contains(c) => {
+                            bytes.push(c.to_ascii_lowercase() as u8 & 0x1f)
+                        }
+                        _ => return None,
+                    }
+                }
+                'M' => {
+                    iter.next();
+                    bytes.push(0x1b);
+                }
+                'e' => bytes.push(0x1b),
+                't' => bytes.push(b'\t'),
+                'n' => bytes.push(b'\n'),
+                'r' => bytes.push(b'\r'),
+                c @ ('\\' | '"' | '\'') => bytes.push(c as u8),
+                _ => return None,
+            }
+        }
+
+        crate::editor::decode_key(&bytes)
+    }
+
+    /// The key as a readline-style sequence that [`Key::parse`] reads back.
+    pub fn sequence(self) -> Option<String> {
+        let char = |c: char| match c {
+            '"' | '\\' => format!("\\{}", c),
+            c => c.to_string(),
+        };
+
+        let seq = match self {
+            Key::Char(c) => char(c),
+            Key::Ctrl(c) => format!("\\C-{}", c),
+            Key::Alt(c) => format!("\\e{}", char(c)),
+            Key::Enter => "\\C-m".to_string(),
+            Key::Escape => "\\e".to_string(),
+            Key::Backspace => "\\C-?".to_string(),
+            Key::AltBackspace => "\\e\\C-?".to_string(),
+            Key::Delete => "\\e[3~".to_string(),
+            Key::Up => "\\e[A".to_string(),
+            Key::Down => "\\e[B".to_string(),
+            Key::Right => "\\e[C".to_string(),
+            Key::Left => "\\e[D".to_string(),
+            Key::Home => "\\e[H".to_string(),
+            Key::End => "\\e[F".to_string(),
+            Key::WordLeft => "\\e[1;5D".to_string(),
+            Key::WordRight => "\\e[1;5C".to_string(),
+            Key::Unknown | Key::Resize => return None,
+        };
+        Some(seq)
+    }
+}
+
+/// An editing command that keys are bound to.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    SelfInsert,
+    AcceptLine,
+    Interrupt,
+    ClearScreen,
+    Complete,
+
+    // Motion
+    BackwardChar,
+    ForwardChar,
+    BeginningOfLine,
+    EndOfLine,
+    BackwardWord,
+    ForwardWord,
+
+    // Deletion, kill and yank
+    BackwardDeleteChar,
+    DeleteChar,
+    DeleteCharOrEof,
+    KillLine,
+    UnixLineDiscard,
+    UnixWordRubout,
+    KillWord,
+    BackwardKillWord,
+    Yank,
+
+    // History
+    PreviousHistory,
+    NextHistory,
+    ReverseSearchHistory,
+
+    // Vi command mode
+    ViCommandMode,
+    ViInsert,
+    ViInsertBeginning,
+    ViAppend,
+    ViAppendEnd,
+    ViForwardWord,
+    ViBackwardWord,
+    ViEndWord,
+    ViFirstNonBlank,
+    ViDelete,
+    ViChange,
+    ViChangeToEnd,
+    ViChangeLine,
+    ViSubstitute,
+    ViPut,
+    ViPutBefore,
+    ViReplaceChar,
+    ViToggleCase,
+}
+
+impl Action {
+    /// The action's name, as `bind` takes it. These follow readline's
+    /// names where readline has an equivalent.
+    pub fn name(self) -> &'static str {
+        NAMES.iter().find(|&&(_, action)| action == self).map_or("", |&(name, _)| name)
+    }
+
+    /// The action called `name`.
+    pub fn from_name(name: &str) -> Option<Action> {
+        NAMES.iter().find(|&&(n, _)| n == name).map(|&(_, action)| action)
+    }
+
+    /// Every action's name, in alphabetical order.
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        let mut names: Vec<&str> = NAMES.iter().map(|&(name, _)| name).collect();
+        names.sort_unstable();
+        names.into_iter()
+    }
+}
+
+/// Maps keys to editing actions.
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+    /// Whether unbound printable keys insert themselves.
+    self_insert: bool,
+}
+
+impl Keymap {
+    /// The action bound to `key`, if any.
+    pub fn lookup(&self, key: Key) -> Option<Action> {
+        match self.bindings.get(&key) {
+            Some(&action) => Some(action),
+            None if self.self_insert && matches!(key, Key::Char(_)) => Some(Action::SelfInsert),
+            None => None,
+        }
+    }
+
+    /// Binds `key` to `action`, replacing any existing binding.
+    pub fn bind(&mut self, key: Key, action: Action) {
+        self.bindings.insert(key, action);
+    }
+
+    /// Every binding, sorted by action name and then key sequence.
+    pub fn bindings(&self) -> Vec<(Key, Action)> {
+        let mut bindings: Vec<(Key, Action)> = self.bindings.iter().map(|(&key, &action)| (key, action)).collect();
+        bindings.sort_by_cached_key(|&(key, action)| (action.name(), key.sequence()));
+        bindings
+    }
+
+    fn new(self_insert: bool, bindings: &[(Key, Action)]) -> Keymap {
+        Keymap { bindings: bindings.iter().copied().collect(), self_insert }
+    }
+
+    /// The default, emacs-style bindings.
+    pub fn emacs() -> Keymap {
+        let mut keymap = Keymap::new(true, COMMON);
+        keymap.bindings.extend([
+            (Key::Ctrl('a'), Action::BeginningOfLine),
+            (Key::Ctrl('e'), Action::EndOfLine),
+            (Key::Ctrl('b'), Action::BackwardChar),
+            (Key::Ctrl('f'), Action::ForwardChar),
+            (Key::Alt('b'), Action::BackwardWord),
+            (Key::Alt('f'), Action::ForwardWord),
+            (Key::Ctrl('d'), Action::DeleteCharOrEof),
+            (Key::Ctrl('k'), Action::KillLine),
+            (Key::Alt('d'), Action::KillWord),
+            (Key::AltBackspace, Action::BackwardKillWord),
+            (Key::Ctrl('y'), Action::Yank),
+            (Key::Ctrl('p'), Action::PreviousHistory),
+            (Key::Ctrl('n'), Action::NextHistory),
+        ]);
+        keymap
+    }
+
+    /// Bindings for vi insert mode.
+    pub fn vi_insert() -> Keymap {
+        let mut keymap = Keymap::new(true, COMMON);
+        keymap.bindings.extend([
+            (Key::Escape, Action::ViCommandMode),
+            (Key::Ctrl('d'), Action::DeleteCharOrEof),
+        ]);
+        keymap
+    }
+
+    /// Bindings for vi command mode.
+    pub fn vi_command() -> Keymap {
+        let mut keymap = Keymap::new(false, COMMON);
+        keymap.bindings.extend([
+            (Key::Char('h'), Action::BackwardChar),
+            (Key::Char('l'), Action::ForwardChar),
+            (Key::Char(' '), Action::ForwardChar),
+            (Key::Char('0'), Action::BeginningOfLine),
+            (Key::Char('^'), Action::ViFirstNonBlank),
+            (Key::Char('$'), Action::EndOfLine),
+            (Key::Char('w'), Action::ViForwardWord),
+            (Key::Char('b'), Action::ViBackwardWord),
+            (Key::Char('e'), Action::ViEndWord),
+            (Key::Char('k'), Action::PreviousHistory),
+            (Key::Char('j'), Action::NextHistory),
+            (Key::Char('i'), Action::ViInsert),
+            (Key::Char('I'), Action::ViInsertBeginning),
+            (Key::Char('a'), Action::ViAppend),
+            (Key::Char('A'), Action::ViAppendEnd),
+            (Key::Char('x'), Action::DeleteChar),
+            (Key::Char('X'), Action::BackwardDeleteChar),
+            (Key::Char('d'), Action::ViDelete),
+            (Key::Char('D'), Action::KillLine),
+            (Key::Char('c'), Action::ViChange),
+            (Key::Char('C'), Action::ViChangeToEnd),
+            (Key::Char('S'), Action::ViChangeLine),
+            (Key::Char('s'), Action::ViSubstitute),
+            (Key::Char('p'), Action::ViPut),
+            (Key::Char('P'), Action::ViPutBefore),
+            (Key::Char('r'), Action::ViReplaceChar),
+            (Key::Char('~'), Action::ViToggleCase),
+            (Key::Ctrl('d'), Action::DeleteCharOrEof),
+        ]);
+        keymap
+    }
+}
+
+/// Bindings shared by every keymap.
+const COMMON: &[(Key, Action)] = &[
+    (Key::Enter, Action::AcceptLine),
+    (Key::Ctrl('c'), Action::Interrupt),
+    (Key::Ctrl('l'), Action::ClearScreen),
+    (Key::Ctrl('i'), Action::Complete),
+    (Key::Ctrl('r'), Action::ReverseSearchHistory),
+    (Key::Backspace, Action::BackwardDeleteChar),
+    (Key::Ctrl('h'), Action::BackwardDeleteChar),
+    (Key::Delete, Action::DeleteChar),
+    (Key::Left, Action::BackwardChar),
+    (Key::Right, Action::ForwardChar),
+    (Key::Home, Action::BeginningOfLine),
+    (Key::End, Action::EndOfLine),
+    (Key::WordLeft, Action::BackwardWord),
+    (Key::WordRight, Action::ForwardWord),
+    (Key::Up, Action::PreviousHistory),
+    (Key::Down, Action::NextHistory),
+    (Key::Ctrl('u'), Action::UnixLineDiscard),
+    (Key::Ctrl('w'), Action::UnixWordRubout),
+];
+
+/// Names for every action.
+const NAMES: &[(&str, Action)] = &[
+    ("self-insert", Action::SelfInsert),
+    ("accept-line", Action::AcceptLine),
+    ("interrupt", Action::Interrupt),
+    ("clear-screen", Action::ClearScreen),
+    ("complete", Action::Complete),
+    ("backward-char", Action::BackwardChar),
+    ("forward-char", Action::ForwardChar),
+    ("beginning-of-line", Action::BeginningOfLine),
+    ("end-of-line", Action::EndOfLine),
+    ("backward-word", Action::BackwardWord),
+    ("forward-word", Action::ForwardWord),
+    ("backward-delete-char", Action::BackwardDeleteChar),
+    ("delete-char", Action::DeleteChar),
+    ("delete-char-or-eof", Action::DeleteCharOrEof),
+    ("kill-line", Action::KillLine),
+    ("unix-line-discard", Action::UnixLineDiscard),
+    ("unix-word-rubout", Action::UnixWordRubout),
+    ("kill-word", Action::KillWord),
+    ("backward-kill-word", Action::BackwardKillWord),
+    ("yank", Action::Yank),
+    ("previous-history", Action::PreviousHistory),
+    ("next-history", Action::NextHistory),
+    ("reverse-search-history", Action::ReverseSearchHistory),
+    ("vi-movement-mode", Action::ViCommandMode),
+    ("vi-insertion-mode", Action::ViInsert),
+    ("vi-insert-beg", Action::ViInsertBeginning),
+    ("vi-append-mode", Action::ViAppend),
+    ("vi-append-eol", Action::ViAppendEnd),
+    ("vi-next-word", Action::ViForwardWord),
+    ("vi-prev-word", Action::ViBackwardWord),
+    ("vi-end-word", Action::ViEndWord),
+    ("vi-first-print", Action::ViFirstNonBlank),
+    ("vi-delete-to", Action::ViDelete),
+    ("vi-change-to", Action::ViChange),
+    ("vi-change-to-eol", Action::ViChangeToEnd),
+    ("vi-change-line", Action::ViChangeLine),
+    ("vi-subst", Action::ViSubstitute),
+    ("vi-put", Action::ViPut),
+    ("vi-put-before", Action::ViPutBefore),
+    ("vi-change-char", Action::ViReplaceChar),
+    ("vi-change-case", Action::ViToggleCase),
+];
@@ -1,141 +1,390 @@
-use std::{iter::Peekable, str::Chars};
+use std::{cell::RefCell, collections::HashSet, fmt, ops::Range, sync::Arc};
 
-use itertools::Itertools;
+use crate::diagnostics::{self, Diagnostic};
+use crate::error::PearshError;
 
-/// Matches either one or two characters, and returns the token.
-/// Changes state of iterator.
-/// 
-/// There are cases where we want to lex two-character sequences, but
-///     need to look ahead one character to determine what we are looking at.
+/// Lexemes already seen, so that a lexeme that comes up again, like a `|`
+/// or a command name, shares the first one's allocation instead of making
+/// another.
+#[derive(Default)]
+struct Interner(HashSet<Arc<str>>);
+
+/// How many lexemes the interner holds on to between calls to
+/// [`tokenize_into`] before it starts over, so that lexing a huge script
+/// doesn't keep every word of it alive.
+const MAX_INTERNED: usize = 4096;
+
+thread_local! {
+    /// Kept from one call to the next, so that lexing line after line
+    /// stops allocating once the common lexemes have been seen.
+    static INTERNER: RefCell<Interner> = RefCell::default();
+}
+
+impl Interner {
+    fn intern(&mut self, lexeme: &str) -> Arc<str> {
+        if let Some(interned) = self.0.get(lexeme) {
+            return interned.clone();
+        }
+        let interned: Arc<str> = Arc::from(lexeme);
+        self.0.insert(interned.clone());
+        interned
+    }
+}
+
+/// Whether `input` stops partway through a command, so more lines are needed
+/// before it can run: inside an open quote, or after a trailing `|`, `&&`
+/// or `||`. Quotes in comments don't count.
+pub fn is_incomplete(input: &str) -> bool {
+    let mut continuation = Continuation::default();
+    continuation.push(input);
+    continuation.is_incomplete()
+}
+
+/// Where a command being read a line at a time has got to, so that each new
+/// line can be checked by itself instead of scanning the whole command
+/// again, as [`is_incomplete`] would.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Continuation {
+    /// The quote that's open, if one is.
+    quote: Option<char>,
+    /// Whether a comment runs to the end of the current line.
+    comment: bool,
+    /// The character before the next one, which tells whether a `#` starts
+    /// a word.
+    previous: Option<char>,
+    /// The last character that isn't whitespace or in a comment.
+    last: Option<char>,
+    /// How many `&`s in a row end at `last`.
+    ampersands: usize,
+}
+
+impl Continuation {
+    /// Reads `text`, which carries on from what was read before.
+    pub fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            let previous = self.previous.replace(c).unwrap_or('\n');
+            if self.comment {
+                self.comment = c != '\n';
+                continue;
+            }
+            match self.quote {
+                Some(q) if c == q => self.quote = None,
+                Some(_) => {}
+                None if c == '\'' || c == '"' => self.quote = Some(c),
+                None if c == '#' && (previous.is_whitespace() || ";|&(".contains(previous)) => {
+                    self.comment = true;
+                    continue;
+                }
+                None if c.is_whitespace() => continue,
+                None => {}
+            }
+            self.ampersands = if c == '&' { self.ampersands + 1 } else { 0 };
+            self.last = Some(c);
+        }
+    }
+
+    /// Whether what's been read so far stops partway through a command.
+    ///
+    /// Outside quotes, the last token is a `|` or `||` if the last
+    /// character is `|`, and a `&&` if an even number of `&`s end it.
+    pub fn is_incomplete(&self) -> bool {
+        self.quote.is_some() || self.last == Some('|') || (self.ampersands > 0 && self.ampersands.is_multiple_of(2))
+    }
+}
+
+/// The spans of the shell words that `tokens` make up.
 ///
-/// Example: !xxx <--- Unparsed characters
-/// .        ^--- If we are here, we need to look ahead to see if we are at an
-/// .             Inequality '!=', or just LogicalNot '!'. 
-/// .
-/// .        In this case, we would use this function like so:
-/// .        match_two_or_one(iter, '=', LogicalNot, Inequality)
-///               
-fn match_two_or_one(iter: &mut Peekable<Chars>, 
-                    second: char, 
-                    if_not_match: TokenKind, 
-                    if_match: TokenKind) -> Token {
-    let first = iter.peek()
-        .expect("The iterator should point to a valid char when this method is called.")
-        .clone();
-
-    // Consume the first character, move to the second
-    iter.next();
-    if let Some(&next_char) = iter.peek() {
-        if next_char == second {
-            iter.next();
-            Token{kind: if_match, lexeme: format!("{}{}",first,second)}
+/// Tokens with no whitespace between them are joined, since the lexer
+/// splits words like `-o` into several tokens.
+pub fn words(tokens: &[Token]) -> Vec<Range<usize>> {
+    let mut words: Vec<Range<usize>> = Vec::new();
+    for token in tokens {
+        match words.last_mut() {
+            Some(word) if word.end == token.span.start => word.end = token.span.end,
+            _ => words.push(token.span.clone()),
         }
-        else { Token{kind: if_not_match, lexeme: first.to_string()} }
     }
-    else { Token{kind: if_not_match, lexeme: first.to_string()} }
+    words
+}
+
+/// The variable name at the start of `text`, if there is one.
+pub fn variable_name(text: &str) -> Option<&str> {
+    let end = text.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(text.len());
+    let name = &text[..end];
+    (!name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit())).then_some(name)
 }
 
-pub fn tokenize(input: String) -> Vec<Token> { 
-    let mut iter = input.chars().peekable();
+/// Splits a `name=value` word into the name and the value, as written.
+pub fn assignment(word: &str) -> Option<(&str, &str)> {
+    let name = variable_name(word)?;
+    let value = word[name.len()..].strip_prefix('=')?;
+    Some((name, value))
+}
+
+/// The variable a word expands, if it's nothing but `$name` or `${name}`,
+/// quoted or not.
+pub fn expansion(word: &str) -> Option<&str> {
+    let word = word.strip_prefix('"').and_then(|word| word.strip_suffix('"')).unwrap_or(word);
+    let name = word.strip_prefix("${").and_then(|word| word.strip_suffix('}')).or_else(|| word.strip_prefix('$'))?;
+    (variable_name(name) == Some(name)).then_some(name)
+}
+
+/// Removes quotes and backslash escapes from a word.
+///
+/// As in sh, a backslash inside double quotes only escapes `$`, `` ` ``,
+/// `"`, `\` and a newline, which it removes, and is kept before anything
+/// else.
+pub fn unquote(word: &str) -> String {
+    let mut unquoted = String::new();
+    let mut quote = None;
+
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some('\'') => unquoted.push(c),
+            None if c == '\'' || c == '"' => quote = Some(c),
+            Some(_) if c == '\\' => match chars.clone().next() {
+                Some('\n') => {
+                    chars.next();
+                }
+                Some(escaped @ ('$' | '`' | '"' | '\\')) => {
+                    chars.next();
+                    unquoted.push(escaped);
+                }
+                _ => unquoted.push(c),
+            },
+            None if c == '\\' => unquoted.extend(chars.next()),
+            _ => unquoted.push(c),
+        }
+    }
+
+    unquoted
+}
+
+/// The spans of the comments in `input`: from a `#` that starts a word,
+/// outside quotes, to the end of its line.
+///
+/// The lexer doesn't know about comments yet, so tools that read scripts
+/// without running them use this to leave them out.
+pub fn comments(input: &str) -> Vec<Range<usize>> {
+    let mut comments: Vec<Range<usize>> = Vec::new();
+    let mut quote = None;
+    let mut previous = '\n';
+    for (i, c) in input.char_indices() {
+        if comments.last().is_some_and(|comment| comment.contains(&i)) {
+            continue;
+        }
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == '#' && (previous.is_whitespace() || ";|&(".contains(previous)) => {
+                comments.push(i..input[i..].find('\n').map_or(input.len(), |n| i + n));
+            }
+            None => {}
+        }
+        previous = c;
+    }
+    comments
+}
+
+/// `input` with its comments blanked out. Everything else stays where it
+/// was, so spans into the result also point into `input`.
+pub fn strip_comments(input: &str) -> String {
+    let mut stripped = input.to_string();
+    for comment in comments(input) {
+        stripped.replace_range(comment.clone(), &" ".repeat(comment.len()));
+    }
+    stripped
+}
+
+/// Errors in `input` that its tokens don't show by themselves: strings
+/// missing their closing quote.
+fn diagnostics(input: &str, tokens: &[Token]) -> Vec<Diagnostic> {
+    tokens
+        .iter()
+        .filter(|token| matches!(token.kind, TokenKind::OneQuoteStr | TokenKind::TwoQuoteStr))
+        .filter(|token| {
+            let text = &input[token.span.clone()];
+            text.len() < 2 || !text.ends_with(&text[..1])
+        })
+        .map(|token| {
+            Diagnostic::error(diagnostics::UNTERMINATED_STRING, "unterminated string", token.span.clone())
+                .note("did you forget a closing quote?")
+        })
+        .collect()
+}
+
+/// Tokenizes `input`, failing if it has errors that [`diagnostics`] finds.
+pub fn lex(input: &str) -> Result<Vec<Token>, PearshError> {
     let mut tokens = Vec::new();
+    lex_into(input, &mut tokens)?;
+    Ok(tokens)
+}
 
-    while let Some(c) = iter.peek() {
-        match c {
+/// Tokenizes `input` into `tokens`, as [`lex`] does, reusing its
+/// allocation. On an error, `tokens` is left holding the tokens anyway.
+pub fn lex_into(input: &str, tokens: &mut Vec<Token>) -> Result<(), PearshError> {
+    tokenize_into(input, tokens);
+    match diagnostics(input, tokens).into_iter().next() {
+        Some(diagnostic) => Err(PearshError::Lex(diagnostic)),
+        None => Ok(()),
+    }
+}
+
+/// Splits `input` into tokens.
+pub fn tokenize(input: String) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    tokenize_into(&input, &mut tokens);
+    tokens
+}
+
+/// Splits `input` into tokens, replacing what's in `tokens`, so that a
+/// caller lexing one line after another can reuse the same `Vec`.
+///
+/// The scanner works on bytes, since every character with a meaning of its
+/// own is ASCII, and only decodes a character where that isn't enough: to
+/// tell whether a non-ASCII one is part of a word or whitespace.
+pub fn tokenize_into(input: &str, tokens: &mut Vec<Token>) {
+    let bytes = input.as_bytes();
+    tokens.clear();
+    let mut interner = INTERNER.take();
+    if interner.0.len() > MAX_INTERNED {
+        interner.0.clear();
+    }
+    let mut push = |kind: TokenKind, lexeme: &str, span: Range<usize>| {
+        tokens.push(Token { kind, lexeme: interner.intern(lexeme), span });
+    };
+
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let start = pos;
+        let kind = match bytes[pos] {
             // Single-character tokens
-            '$' => { tokens.push(Token{kind: TokenKind::Dollar, lexeme: c.to_string()});
-                     iter.next(); }
-            ';' => { tokens.push(Token{kind: TokenKind::Semicolon, lexeme: c.to_string()});
-                     iter.next(); }
-            '(' => { tokens.push(Token{kind: TokenKind::LParen, lexeme: c.to_string()});
-                     iter.next(); }
-            ')' => { tokens.push(Token{kind: TokenKind::RParen, lexeme: c.to_string()});
-                     iter.next(); }
-            '{' => { tokens.push(Token{kind: TokenKind::LCurly, lexeme: c.to_string()});
-                     iter.next(); }
-            '}' => { tokens.push(Token{kind: TokenKind::RCurly, lexeme: c.to_string()});
-                     iter.next(); }
-            '[' => { tokens.push(Token{kind: TokenKind::LSquare, lexeme: c.to_string()});
-                     iter.next(); }
-            ']' => { tokens.push(Token{kind: TokenKind::RSquare, lexeme: c.to_string()});
-                     iter.next(); }
-
-            // Double-character tokens
-            '=' => { tokens.push(match_two_or_one(&mut iter, '=', 
-                     TokenKind::Assign, TokenKind::Equality)); }
-            '!' => { tokens.push(match_two_or_one(&mut iter, '=', 
-                     TokenKind::LogicalNot, TokenKind::Inequality)); }
-            '|' => { tokens.push(match_two_or_one(&mut iter, '|', 
-                     TokenKind::Pipe, TokenKind::LogicalOr)); }
-            '&' => { tokens.push(match_two_or_one(&mut iter, '&', 
-                     TokenKind::Ampersand, TokenKind::LogicalAnd)); }
-            '>' => { tokens.push(match_two_or_one(&mut iter, '>', 
-                     TokenKind::Redirect, TokenKind::CatRedirect)); }
-
-            // Words
-            c if c.is_alphanumeric() => {
-                let lexeme: String = iter
-                    .by_ref()
-                    .peeking_take_while(|&x| x.is_alphanumeric())
-                    .collect();
-
-                // Keywords
-                let kind = match lexeme.as_str() {
-                    "while"  => {TokenKind::While}
-                    "for"    => {TokenKind::For}
-                    "if"     => {TokenKind::If}
-                    "elif"   => {TokenKind::Elif}
-                    "else"   => {TokenKind::Else}
-                    _ => {TokenKind::Word}
-                };
-
-                tokens.push(Token{kind, lexeme});
-                
-            }
+            b'$' => Some(TokenKind::Dollar),
+            b';' => Some(TokenKind::Semicolon),
+            b'(' => Some(TokenKind::LParen),
+            b')' => Some(TokenKind::RParen),
+            b'{' => Some(TokenKind::LCurly),
+            b'}' => Some(TokenKind::RCurly),
+            b'[' => Some(TokenKind::LSquare),
+            b']' => Some(TokenKind::RSquare),
+            _ => None,
+        };
+        if let Some(kind) = kind {
+            pos += 1;
+            push(kind, &input[start..pos], start..pos);
+            continue;
+        }
+
+        // Double-character tokens, like `!=`, whose first character is a
+        // token of its own when the second doesn't follow.
+        let pair = match bytes[pos] {
+            b'=' => Some((b'=', TokenKind::Assign, TokenKind::Equality)),
+            b'!' => Some((b'=', TokenKind::LogicalNot, TokenKind::Inequality)),
+            b'|' => Some((b'|', TokenKind::Pipe, TokenKind::LogicalOr)),
+            b'&' => Some((b'&', TokenKind::Ampersand, TokenKind::LogicalAnd)),
+            b'>' => Some((b'>', TokenKind::Redirect, TokenKind::CatRedirect)),
+            _ => None,
+        };
+        if let Some((second, one, two)) = pair {
+            let kind = if bytes.get(pos + 1) == Some(&second) {
+                pos += 2;
+                two
+            } else {
+                pos += 1;
+                one
+            };
+            push(kind, &input[start..pos], start..pos);
+            continue;
+        }
 
-            // Strings
-            '\'' | '"' => {
-                let ch = c.clone();
-
-                iter.next();
-                let word = iter
-                    .by_ref()
-                    .take_while(|&x| x != ch)
-                    .collect();
-
-                match ch {
-                    '\'' => { tokens.push(Token{kind: TokenKind::OneQuoteStr, 
-                                                lexeme: word})}
-                    '"'  => { tokens.push(Token{kind: TokenKind::TwoQuoteStr, 
-                                                lexeme: format!("\"{}\"", word) })}
-                    _ => {}
+        match bytes[pos] {
+            // Strings, up to the closing quote or the end of the input.
+            // Single-quoted strings lose their quotes, and unterminated
+            // double-quoted ones gain a closing quote.
+            quote @ (b'\'' | b'"') => {
+                let close = bytes[pos + 1..].iter().position(|&b| b == quote);
+                pos = close.map_or(bytes.len(), |close| pos + 1 + close + 1);
+                let text = &input[start..pos];
+                match (quote, close) {
+                    (b'\'', _) => {
+                        let contents = &text[1..text.len() - usize::from(close.is_some())];
+                        push(TokenKind::OneQuoteStr, contents, start..pos);
+                    }
+                    (_, Some(_)) => push(TokenKind::TwoQuoteStr, text, start..pos),
+                    (_, None) => push(TokenKind::TwoQuoteStr, &format!("{}\"", text), start..pos),
                 }
             }
-                
 
             // It might be useful if we separate this case from other whitespace
-            '\n'=> { tokens.push(Token{kind: TokenKind::Newline, lexeme: "\\n".to_string()});
-                     iter.next(); }
-
-            // Skip whitespace
-            c if c.is_whitespace() => {
-                iter.next();
+            b'\n' => {
+                pos += 1;
+                push(TokenKind::Newline, "\\n", start..pos);
             }
 
-            // Unrecognized
-            _ => { tokens.push(Token { kind: TokenKind::Unknown, lexeme: c.to_string()});
-                     iter.next(); }
+            _ => {
+                let c = char_at(input, pos);
+                if c.is_alphanumeric() {
+                    pos += c.len_utf8();
+                    while let Some(c) = (pos < bytes.len()).then(|| char_at(input, pos)).filter(|c| c.is_alphanumeric()) {
+                        pos += c.len_utf8();
+                    }
+                    let word = &input[start..pos];
+                    push(keyword(word).unwrap_or(TokenKind::Word), word, start..pos);
+                } else if c.is_whitespace() {
+                    pos += c.len_utf8();
+                } else {
+                    pos += c.len_utf8();
+                    push(TokenKind::Unknown, &input[start..pos], start..pos);
+                }
+            }
         }
     }
+    INTERNER.set(interner);
+}
 
-    tokens
+/// The character starting at byte `pos` of `input`, decoding it only if it
+/// isn't ASCII.
+fn char_at(input: &str, pos: usize) -> char {
+    match input.as_bytes()[pos] {
+        byte if byte.is_ascii() => byte as char,
+        _ => input[pos..].chars().next().expect("`pos` is at the start of a character"),
+    }
+}
+
+/// The keyword `word` is, if it's one.
+fn keyword(word: &str) -> Option<TokenKind> {
+    match word {
+        "while" => Some(TokenKind::While),
+        "for" => Some(TokenKind::For),
+        "if" => Some(TokenKind::If),
+        "elif" => Some(TokenKind::Elif),
+        "else" => Some(TokenKind::Else),
+        "int" => Some(TokenKind::TypeInt),
+        "long" => Some(TokenKind::TypeLong),
+        "char" => Some(TokenKind::TypeChar),
+        "float" => Some(TokenKind::TypeFloat),
+        "double" => Some(TokenKind::TypeDouble),
+        "bool" => Some(TokenKind::TypeBool),
+        "const" => Some(TokenKind::Const),
+        "let" => Some(TokenKind::Let),
+        _ => None,
+    }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Token {
     pub kind: TokenKind,
-    pub lexeme: String
+    /// The token's text. Tokens from the same input with the same text
+    /// share it.
+    pub lexeme: Arc<str>,
+    /// Byte range of the token in the input
+    pub span: Range<usize>,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TokenKind {
     // Syntax
     Word,
@@ -172,6 +421,7 @@ pub enum TokenKind {
     TypeChar,
     TypeFloat,
     TypeDouble,
+    TypeBool,
 
     // Etc
     Newline,
@@ -181,4 +431,249 @@ pub enum TokenKind {
     If,
     Elif,
     Else,
+    Const,
+    Let,
+}
+
+impl TokenKind {
+    /// The kind's name, as shown in token dumps. Names don't change, so
+    /// they're safe to compare against or store.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::Word => "Word",
+            TokenKind::Semicolon => "Semicolon",
+            TokenKind::Ampersand => "Ampersand",
+            TokenKind::Dollar => "Dollar",
+            TokenKind::Assign => "Assign",
+            TokenKind::OneQuoteStr => "OneQuoteStr",
+            TokenKind::TwoQuoteStr => "TwoQuoteStr",
+            TokenKind::Pipe => "Pipe",
+            TokenKind::Redirect => "Redirect",
+            TokenKind::Equality => "Equality",
+            TokenKind::Inequality => "Inequality",
+            TokenKind::LogicalOr => "LogicalOr",
+            TokenKind::LogicalAnd => "LogicalAnd",
+            TokenKind::LogicalNot => "LogicalNot",
+            TokenKind::CatRedirect => "CatRedirect",
+            TokenKind::LParen => "LParen",
+            TokenKind::RParen => "RParen",
+            TokenKind::LCurly => "LCurly",
+            TokenKind::RCurly => "RCurly",
+            TokenKind::LSquare => "LSquare",
+            TokenKind::RSquare => "RSquare",
+            TokenKind::TypeInt => "TypeInt",
+            TokenKind::TypeLong => "TypeLong",
+            TokenKind::TypeChar => "TypeChar",
+            TokenKind::TypeFloat => "TypeFloat",
+            TokenKind::TypeDouble => "TypeDouble",
+            TokenKind::TypeBool => "TypeBool",
+            TokenKind::Newline => "Newline",
+            TokenKind::Unknown => "Unknown",
+            TokenKind::While => "While",
+            TokenKind::For => "For",
+            TokenKind::If => "If",
+            TokenKind::Elif => "Elif",
+            TokenKind::Else => "Else",
+            TokenKind::Const => "Const",
+            TokenKind::Let => "Let",
+        }
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Shows a token as its kind and lexeme, like `Word("echo")`.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}({:?})", self.kind, self.lexeme)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The kind, lexeme and span of each token in `input`.
+    fn lexed(input: &str) -> Vec<(TokenKind, String, Range<usize>)> {
+        tokenize(input.to_string()).into_iter().map(|token| (token.kind, token.lexeme.to_string(), token.span)).collect()
+    }
+
+    #[test]
+    fn single_character_operators() {
+        assert_eq!(
+            lexed("a|b&c;d>e"),
+            vec![
+                (TokenKind::Word, "a".to_string(), 0..1),
+                (TokenKind::Pipe, "|".to_string(), 1..2),
+                (TokenKind::Word, "b".to_string(), 2..3),
+                (TokenKind::Ampersand, "&".to_string(), 3..4),
+                (TokenKind::Word, "c".to_string(), 4..5),
+                (TokenKind::Semicolon, ";".to_string(), 5..6),
+                (TokenKind::Word, "d".to_string(), 6..7),
+                (TokenKind::Redirect, ">".to_string(), 7..8),
+                (TokenKind::Word, "e".to_string(), 8..9),
+            ]
+        );
+    }
+
+    #[test]
+    fn double_character_operators() {
+        assert_eq!(
+            lexed("|| && >> == !="),
+            vec![
+                (TokenKind::LogicalOr, "||".to_string(), 0..2),
+                (TokenKind::LogicalAnd, "&&".to_string(), 3..5),
+                (TokenKind::CatRedirect, ">>".to_string(), 6..8),
+                (TokenKind::Equality, "==".to_string(), 9..11),
+                (TokenKind::Inequality, "!=".to_string(), 12..14),
+            ]
+        );
+    }
+
+    #[test]
+    fn operators_pair_up_from_the_left() {
+        let kinds: Vec<TokenKind> = lexed("|||&&&").into_iter().map(|(kind, _, _)| kind).collect();
+        assert_eq!(kinds, [TokenKind::LogicalOr, TokenKind::Pipe, TokenKind::LogicalAnd, TokenKind::Ampersand]);
+    }
+
+    #[test]
+    fn brackets_and_newlines() {
+        assert_eq!(
+            lexed("{(\n)}"),
+            vec![
+                (TokenKind::LCurly, "{".to_string(), 0..1),
+                (TokenKind::LParen, "(".to_string(), 1..2),
+                (TokenKind::Newline, "\\n".to_string(), 2..3),
+                (TokenKind::RParen, ")".to_string(), 3..4),
+                (TokenKind::RCurly, "}".to_string(), 4..5),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_strings() {
+        assert_eq!(
+            lexed("'one two' \"three\""),
+            vec![
+                (TokenKind::OneQuoteStr, "one two".to_string(), 0..9),
+                (TokenKind::TwoQuoteStr, "\"three\"".to_string(), 10..17),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_strings_run_to_the_end_of_the_input() {
+        assert_eq!(lexed("echo 'abc"), vec![
+            (TokenKind::Word, "echo".to_string(), 0..4),
+            (TokenKind::OneQuoteStr, "abc".to_string(), 5..9),
+        ]);
+        assert_eq!(lexed("\"abc\nd"), vec![(TokenKind::TwoQuoteStr, "\"abc\nd\"".to_string(), 0..6)]);
+    }
+
+    #[test]
+    fn unterminated_strings_are_errors() {
+        for input in ["echo 'abc", "echo \"abc", "'", "\""] {
+            match lex(input) {
+                Err(PearshError::Lex(diagnostic)) => {
+                    assert_eq!(diagnostic.code, diagnostics::UNTERMINATED_STRING);
+                    assert_eq!(diagnostic.span.end, input.len(), "{:?}", input);
+                }
+                other => panic!("{:?} lexed as {:?}", input, other),
+            }
+        }
+        assert!(lex("echo 'abc' \"def\"").is_ok());
+    }
+
+    #[test]
+    fn non_ascii_words() {
+        assert_eq!(
+            lexed("café naïve 日本語"),
+            vec![
+                (TokenKind::Word, "café".to_string(), 0..5),
+                (TokenKind::Word, "naïve".to_string(), 6..12),
+                (TokenKind::Word, "日本語".to_string(), 13..22),
+            ]
+        );
+    }
+
+    #[test]
+    fn non_ascii_whitespace_separates_words() {
+        // U+00A0 NO-BREAK SPACE and U+3000 IDEOGRAPHIC SPACE
+        assert_eq!(
+            lexed("a\u{a0}b\u{3000}c"),
+            vec![
+                (TokenKind::Word, "a".to_string(), 0..1),
+                (TokenKind::Word, "b".to_string(), 3..4),
+                (TokenKind::Word, "c".to_string(), 7..8),
+            ]
+        );
+    }
+
+    #[test]
+    fn non_ascii_symbols_are_unknown() {
+        assert_eq!(lexed("→"), vec![(TokenKind::Unknown, "→".to_string(), 0..3)]);
+    }
+
+    #[test]
+    fn keywords() {
+        let kinds: Vec<TokenKind> = lexed("if elif else while for const let int bool").into_iter().map(|(kind, _, _)| kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::If,
+                TokenKind::Elif,
+                TokenKind::Else,
+                TokenKind::While,
+                TokenKind::For,
+                TokenKind::Const,
+                TokenKind::Let,
+                TokenKind::TypeInt,
+                TokenKind::TypeBool,
+            ]
+        );
+    }
+
+    #[test]
+    fn incomplete_input() {
+        for input in ["echo 'abc\n", "echo \"abc\n", "a |\n", "a ||\n", "a &&\n", "a | # more to come\n"] {
+            assert!(is_incomplete(input), "{:?}", input);
+        }
+        for input in ["echo 'abc'\n", "a &\n", "a &&&\n", "# don't\n", "echo a # it's done\n", "echo '#'\n"] {
+            assert!(!is_incomplete(input), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn continuation_matches_is_incomplete_line_by_line() {
+        let input = "# don't\necho 'a\nb' |\n  c &&\nd\n";
+        let mut continuation = Continuation::default();
+        let mut read = 0;
+        for line in input.split_inclusive('\n') {
+            continuation.push(line);
+            read += line.len();
+            assert_eq!(continuation.is_incomplete(), is_incomplete(&input[..read]), "{:?}", &input[..read]);
+        }
+        assert!(!continuation.is_incomplete());
+    }
+
+    #[test]
+    fn unquoting() {
+        for (word, unquoted) in [
+            ("plain", "plain"),
+            ("'a b'c", "a bc"),
+            ("\"a b\"", "a b"),
+            ("'a\\b'", "a\\b"),
+            ("a\\ b\\\\", "a b\\"),
+            ("\"a\\b\"", "a\\b"),
+            ("\"\\$x \\` \\\" \\\\\"", "$x ` \" \\"),
+            ("\"a\\\nb\"", "ab"),
+            ("\"it's\"'\"'", "it's\""),
+        ] {
+            assert_eq!(unquote(word), unquoted, "{:?}", word);
+        }
+    }
 }
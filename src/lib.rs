@@ -0,0 +1,35 @@
+//! The pearsh shell, as a library.
+//!
+//! [`lexer`] turns input into tokens, [`shell`] runs it, and [`lint`] and
+//! [`format`](mod@format) check and tidy scripts without running them. Errors are
+//! [`PearshError`]s, and those that point into the input render as
+//! [`diagnostics`] with source context. There's no parser yet, so
+//! everything downstream of the lexer works on tokens.
+
+pub mod command;
+pub mod diagnostics;
+pub mod dialect;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+pub mod hooks;
+pub mod lexer;
+pub mod lint;
+pub mod shell;
+pub mod typecheck;
+pub mod value;
+
+mod complete;
+mod editor;
+mod highlight;
+mod history;
+mod keymap;
+mod prompt;
+
+pub use command::PearshCommand;
+pub use error::PearshError;
+pub use hooks::ShellHook;
+pub use lexer::tokenize;
+pub use shell::{CommandHandler, ExitStatus, NativeFn, Options, Output, Shell, ShellState};
+pub use value::{Type, Value};
@@ -0,0 +1,301 @@
+use std::ops::Range;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::{self, Token, TokenKind};
+
+/// One command of a script, with the separators on either side of it.
+pub(crate) struct Command<'a> {
+    pub(crate) tokens: &'a [Token],
+    before: Option<&'a TokenKind>,
+    after: Option<&'a TokenKind>,
+}
+
+impl Command<'_> {
+    pub(crate) fn span(&self) -> Range<usize> {
+        let start = self.tokens.first().map_or(0, |t| t.span.start);
+        let end = self.tokens.last().map_or(0, |t| t.span.end);
+        start..end
+    }
+
+    /// Whether the command runs unconditionally after the one before it.
+    fn is_unconditional(&self) -> bool {
+        matches!(self.before, None | Some(TokenKind::Newline | TokenKind::Semicolon))
+            && matches!(self.after, None | Some(TokenKind::Newline | TokenKind::Semicolon))
+    }
+}
+
+/// Looks for things in `source` that are allowed, but are probably
+/// mistakes, returning a warning for each in the order they appear.
+///
+/// This works on tokens rather than a syntax tree, so it errs on the side
+/// of saying nothing when the shape of a script isn't clear.
+pub fn lint(source: &str) -> Vec<Diagnostic> {
+    let source = lexer::strip_comments(source);
+    let tokens = lexer::tokenize(source.clone());
+    let commands = commands(&tokens);
+
+    let mut warnings: Vec<Diagnostic> = Vec::new();
+    warnings.extend(unquoted_expansions(&source, &commands));
+    warnings.extend(commands.iter().filter_map(|command| useless_cat(&source, command)));
+    warnings.extend(unreachable(&source, &commands));
+    warnings.extend(unused_assignments(&source, &commands));
+    warnings.extend(commands.iter().filter_map(|command| status_comparison(&source, command)));
+
+    warnings.sort_by_key(|warning| warning.span.start);
+    warnings
+}
+
+/// Splits `tokens` into commands at newlines and command separators.
+pub(crate) fn commands(tokens: &[Token]) -> Vec<Command<'_>> {
+    let mut commands = Vec::new();
+    let mut start = 0;
+    let mut before = None;
+    for (i, token) in tokens.iter().enumerate() {
+        let is_separator = matches!(
+            token.kind,
+            TokenKind::Newline
+                | TokenKind::Semicolon
+                | TokenKind::Pipe
+                | TokenKind::Ampersand
+                | TokenKind::LogicalAnd
+                | TokenKind::LogicalOr
+        );
+        if is_separator {
+            if i > start {
+                commands.push(Command { tokens: &tokens[start..i], before, after: Some(&token.kind) });
+            }
+            start = i + 1;
+            before = Some(&token.kind);
+        }
+    }
+    if start < tokens.len() {
+        commands.push(Command { tokens: &tokens[start..], before, after: None });
+    }
+    commands
+}
+
+/// If `word` assigns to a variable, the name it assigns to.
+fn assigned_name(word: &str) -> Option<&str> {
+    lexer::assignment(word).map(|(name, _)| name)
+}
+
+/// W001: `$name` or `${name}` outside double quotes, which is split into
+/// several words if the value contains spaces.
+///
+/// Assignments are left alone, since their values aren't split.
+fn unquoted_expansions(source: &str, commands: &[Command]) -> Vec<Diagnostic> {
+    let words: Vec<Range<usize>> = commands.iter().flat_map(|command| lexer::words(command.tokens)).collect();
+    commands
+        .iter()
+        .flat_map(|command| command.tokens)
+        .filter(|token| matches!(token.kind, TokenKind::Dollar))
+        .filter_map(|dollar| {
+            let rest = &source[dollar.span.end..];
+            let span = match rest.strip_prefix('{') {
+                Some(braced) => {
+                    lexer::variable_name(braced)?;
+                    dollar.span.start..dollar.span.end + 1 + braced.find('}')? + 1
+                }
+                None => dollar.span.start..dollar.span.end + lexer::variable_name(rest)?.len(),
+            };
+
+            let word = words.iter().find(|word| word.contains(&dollar.span.start))?;
+            if assigned_name(&source[word.clone()]).is_some() {
+                return None;
+            }
+
+            let expansion = &source[span.clone()];
+            Some(
+                Diagnostic::warning(diagnostics::UNQUOTED_EXPANSION, "unquoted variable expansion", span)
+                    .note(format!("the value is split into words and globbed; quote it as \"{}\"", expansion)),
+            )
+        })
+        .collect()
+}
+
+/// W002: `cat file | command`, where `command < file` would do.
+fn useless_cat(source: &str, command: &Command) -> Option<Diagnostic> {
+    let words = lexer::words(command.tokens);
+    let [cat, file] = words.as_slice() else {
+        return None;
+    };
+    if &source[cat.clone()] != "cat" || source[file.clone()].starts_with('-') {
+        return None;
+    }
+    if !matches!(command.after, Some(TokenKind::Pipe)) {
+        return None;
+    }
+
+    Some(
+        Diagnostic::warning(diagnostics::USELESS_CAT, "useless use of `cat`", command.span())
+            .note(format!("redirect the file into the next command instead, with `< {}`", &source[file.clone()])),
+    )
+}
+
+/// W003: the first command after an `exit` that always runs, which never
+/// does.
+///
+/// An `exit` inside braces, parentheses or an `if`, `while`, `for` or
+/// `case` might not run, so only top-level ones count.
+fn unreachable(source: &str, commands: &[Command]) -> Option<Diagnostic> {
+    let mut depth = 0i32;
+    let mut exit: Option<&Command> = None;
+    for command in commands {
+        let first = lexer::words(command.tokens).first().map(|word| &source[word.clone()]);
+
+        if let Some(exit) = exit {
+            if matches!(first, Some("fi" | "done" | "esac" | ";;")) || depth != 0 {
+                return None;
+            }
+            let line = source[..exit.span().start].matches('\n').count() + 1;
+            return Some(
+                Diagnostic::warning(diagnostics::UNREACHABLE, "unreachable code", command.span())
+                    .note(format!("the `exit` on line {} always ends the script first", line)),
+            );
+        }
+
+        let braced = command.tokens.iter().any(|t| matches!(t.kind, TokenKind::LCurly));
+        let opens_block = matches!(
+            command.tokens.first().map(|t| &t.kind),
+            Some(TokenKind::If | TokenKind::While | TokenKind::For)
+        ) || matches!(first, Some("case" | "until"));
+        if opens_block && !braced {
+            depth += 1;
+        }
+        if matches!(first, Some("fi" | "done" | "esac")) {
+            depth -= 1;
+        }
+        for token in command.tokens {
+            match token.kind {
+                TokenKind::LCurly | TokenKind::LParen => depth += 1,
+                TokenKind::RCurly | TokenKind::RParen => depth -= 1,
+                _ => {}
+            }
+        }
+
+        if depth == 0 && first == Some("exit") && command.is_unconditional() {
+            exit = Some(command);
+        }
+    }
+    None
+}
+
+/// W004: a variable that's assigned but never expanded or named again.
+///
+/// Only commands made up of nothing but assignments count; `name=value
+/// command` sets `name` for `command` to use. All-uppercase names are
+/// skipped too, since they're usually meant for other programs.
+fn unused_assignments(source: &str, commands: &[Command]) -> Vec<Diagnostic> {
+    let mut warnings = Vec::new();
+    for command in commands {
+        let words = lexer::words(command.tokens);
+        if !words.iter().all(|word| assigned_name(&source[word.clone()]).is_some()) {
+            continue;
+        }
+
+        for word in &words {
+            let Some(name) = assigned_name(&source[word.clone()]) else {
+                continue;
+            };
+            if name.chars().all(|c| !c.is_lowercase()) || is_used(source, name, word.start) {
+                continue;
+            }
+            warnings.push(
+                Diagnostic::warning(
+                    diagnostics::UNUSED_ASSIGNMENT,
+                    format!("`{}` is assigned but never used", name),
+                    word.start..word.start + name.len(),
+                )
+                .note("remove the assignment, or export it if another program needs it"),
+            );
+        }
+    }
+    warnings
+}
+
+/// Whether `name`, assigned at `at`, appears anywhere else in `source`,
+/// whether expanded (even inside a string) or as a word of its own, as in
+/// `export name`.
+fn is_used(source: &str, name: &str, at: usize) -> bool {
+    source.match_indices(name).any(|(i, _)| {
+        let before = source[..i].chars().next_back();
+        let after = source[i + name.len()..].chars().next();
+        let whole = !before.is_some_and(|c| c.is_alphanumeric() || c == '_')
+            && !after.is_some_and(|c| c.is_alphanumeric() || c == '_');
+        i != at && whole && !matches!(after, Some('='))
+    })
+}
+
+/// W005: `$?` compared against something, as in `[ $? -eq 0 ]`, where
+/// testing the command itself is clearer and can't pick up the status of
+/// the wrong command.
+fn status_comparison(source: &str, command: &Command) -> Option<Diagnostic> {
+    let compares = command.tokens.iter().any(|t| matches!(t.kind, TokenKind::Equality | TokenKind::Inequality))
+        || lexer::words(command.tokens)
+            .iter()
+            .any(|word| matches!(&source[word.clone()], "-eq" | "-ne" | "=" | "!="));
+    if !compares {
+        return None;
+    }
+
+    let dollar = command
+        .tokens
+        .iter()
+        .find(|t| matches!(t.kind, TokenKind::Dollar) && source[t.span.end..].starts_with('?'))?;
+    Some(
+        Diagnostic::warning(
+            diagnostics::STATUS_COMPARISON,
+            "comparison against `$?`",
+            dollar.span.start..dollar.span.end + 1,
+        )
+        .note("test the command directly instead, as in `if command; then`"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The code and span of each warning `lint` gives for `source`.
+    fn warnings(source: &str) -> Vec<(&'static str, Range<usize>)> {
+        lint(source).into_iter().map(|warning| (warning.code, warning.span)).collect()
+    }
+
+    #[test]
+    fn unquoted_expansions() {
+        assert_eq!(warnings("echo $name ${other}"), [
+            (diagnostics::UNQUOTED_EXPANSION, 5..10),
+            (diagnostics::UNQUOTED_EXPANSION, 11..19),
+        ]);
+        assert_eq!(warnings("echo \"$name\"\ncopy=$name\nshow \"$copy\""), []);
+    }
+
+    #[test]
+    fn useless_cat() {
+        assert_eq!(warnings("cat file | wc -l"), [(diagnostics::USELESS_CAT, 0..8)]);
+        assert_eq!(warnings("cat file\ncat -n file | wc -l\ncat a b | wc -l"), []);
+    }
+
+    #[test]
+    fn unreachable_code() {
+        assert_eq!(warnings("exit 0\ntrue"), [(diagnostics::UNREACHABLE, 7..11)]);
+        assert_eq!(warnings("true || exit 1\ntrue\nif false; then exit; fi\ntrue\n{ exit; }\ntrue"), []);
+    }
+
+    #[test]
+    fn unused_assignments() {
+        assert_eq!(warnings("count=1"), [(diagnostics::UNUSED_ASSIGNMENT, 0..5)]);
+        assert_eq!(warnings("count=1\nshow \"$count\"\nPATH=/bin\nname=1 command"), []);
+    }
+
+    #[test]
+    fn status_comparisons() {
+        assert_eq!(warnings("[ $? -eq 0 ]"), [(diagnostics::STATUS_COMPARISON, 2..4)]);
+        assert_eq!(warnings("show $?\n[ \"$x\" -eq 0 ]"), []);
+    }
+
+    #[test]
+    fn comments_are_left_out() {
+        assert_eq!(warnings("true # echo $name; it's exit 0"), []);
+    }
+}
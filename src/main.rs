@@ -1,26 +1,195 @@
-use std::io;
-
-mod lexer;
-pub use lexer::tokenize;
-
-fn print_lex_results(input: String) {
-    lexer::tokenize(input)
-        .iter()
-        .for_each(|x| {
-            let lexeme = format!("[{}]", x.lexeme);
-            print!("{0: <10}: ", lexeme);
-            println!("{:?}", x.kind);
-        });
+use std::{env, fs, process};
+use std::io::{self, IsTerminal};
+
+use pearsh::diagnostics::{Diagnostic, Location};
+use pearsh::dialect::Dialect;
+use pearsh::shell::{self, Options, Shell};
+use pearsh::{format, lexer, lint, typecheck};
+
+/// Reads each script in `paths` and runs `check` over it, printing the
+/// diagnostics it returns, along with any from lexing the script. The
+/// status is 1 if there were any, or 2 if a script couldn't be read.
+fn check_files(paths: &[String], check: fn(&str) -> Vec<Diagnostic>) -> i32 {
+    let mut status = 0;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("pearsh: {}: {}", path, err);
+                status = 2;
+                continue;
+            }
+        };
+
+        // Comments are left out, as the checkers leave them out, so that a
+        // quote in one isn't taken for the start of a string.
+        let location = [Location { name: path.clone(), line: 1 }];
+        if let Err(err) = lexer::lex(&lexer::strip_comments(&source)) {
+            err.report(&source, &location);
+            status = status.max(1);
+        }
+        for diagnostic in check(&source) {
+            diagnostic.emit(&source, &location);
+            status = status.max(1);
+        }
+    }
+    status
 }
 
-fn main() {
-    loop {
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+/// Formats scripts in place, or with `--check`, lists the ones that would
+/// change. The status is 1 if `--check` found any, or 2 if a script
+/// couldn't be read, lexed or written.
+fn format_files(args: &[String]) -> i32 {
+    let (check, paths) = match args {
+        [flag, paths @ ..] if flag == "--check" => (true, paths),
+        paths => (false, paths),
+    };
+    if paths.is_empty() {
+        eprintln!("usage: pearsh fmt [--check] file...");
+        return 2;
+    }
 
-        print_lex_results(input);
+    let mut status = 0;
+    for path in paths {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("pearsh: {}: {}", path, err);
+                status = 2;
+                continue;
+            }
+        };
 
-        println!();
+        // Formatting a script that doesn't lex could only make it worse.
+        // Comments are left out, as `format` keeps them as they are.
+        if let Err(err) = lexer::lex(&lexer::strip_comments(&source)) {
+            err.report(&source, &[Location { name: path.clone(), line: 1 }]);
+            status = 2;
+            continue;
+        }
+
+        let formatted = format::format(&source);
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{}", path);
+            status = status.max(1);
+        } else if let Err(err) = fs::write(path, formatted) {
+            eprintln!("pearsh: {}: {}", path, err);
+            status = 2;
+        }
     }
+    status
 }
 
+fn main() {
+    let mut args = env::args();
+
+    // Like other shells, pearsh is a login shell when its name starts with
+    // `-` (as login(1) runs it) or when given `--login`. `--tokens` starts
+    // with `lexdebug` on, and `--dialect=bash` treats every script as bash
+    // rather than going by its `#!` line. Single-letter flags can be
+    // combined, as in `-ec`, and `--` ends the options, as with sh.
+    let mut login = args.next().is_some_and(|name| name.starts_with('-'));
+    let mut args: Vec<String> = args.collect();
+    let mut options = Options::default();
+    let mut string = false;
+    let mut ended = false;
+    while let Some(arg) = args.first() {
+        match arg.as_str() {
+            "--" => {
+                args.remove(0);
+                ended = true;
+                break;
+            }
+            "--login" => login = true,
+            "--tokens" => options.lexdebug = true,
+            flags if flags.len() > 1 && flags.starts_with('-') && flags[1..].chars().all(|c| "celx".contains(c)) => {
+                for flag in flags[1..].chars() {
+                    match flag {
+                        'c' => string = true,
+                        'e' => options.errexit = true,
+                        'l' => login = true,
+                        _ => options.xtrace = true,
+                    }
+                }
+            }
+            arg if arg.starts_with("--dialect=") => {
+                let name = &arg["--dialect=".len()..];
+                let Some(dialect) = Dialect::from_name(name) else {
+                    eprintln!("pearsh: {}: unknown dialect (expected pearsh or bash)", name);
+                    process::exit(2);
+                };
+                options.dialect = Some(dialect);
+            }
+            _ => break,
+        }
+        args.remove(0);
+    }
+    let mut shell = Shell::new(options);
+
+    // Only pearsh's own profile is run. `/etc/profile` is written for sh,
+    // and relies on `if` and `for`, which pearsh can't run yet.
+    let exited = login
+        .then(|| shell::home_file(".pearsh_profile").and_then(|path| shell::source_file(&path, &mut shell)))
+        .flatten();
+
+    // `pearsh -c command [name args...]` runs a command string, with `$0`
+    // set to `name` and the positional parameters to `args`,
+    // `pearsh --lint file...` checks scripts, `pearsh --check-types
+    // file...` checks their typed variables and constants, `pearsh fmt
+    // file...` formats them, and `pearsh file [args...]` runs a script.
+    // Otherwise commands come from stdin, interactively if it's a
+    // terminal. Unless something exits early, the shell exits with the
+    // status of the last command it ran: 2 for a syntax error, 127 for a
+    // command that wasn't found and 126 for one that couldn't be run, as
+    // with sh.
+    let status = match (exited, args.as_slice()) {
+        (Some(status), _) => status,
+        (None, [command, rest @ ..]) if string => {
+            match rest.split_first() {
+                Some((name, args)) => shell.set_args(name, args),
+                None => shell.set_args("pearsh", &[] as &[&str]),
+            }
+            if shell::runs_as(command, "-c", &mut shell) {
+                shell::run_string(command, "-c", &mut shell).unwrap_or(shell.status())
+            } else {
+                2
+            }
+        }
+        (None, []) if string => {
+            eprintln!("pearsh: -c: option requires an argument");
+            2
+        }
+        // Checks scripts without running them, for mistakes or for values
+        // that don't fit the types of their variables.
+        (None, [flag, paths @ ..]) if flag == "--lint" && !paths.is_empty() => check_files(paths, lint::lint),
+        (None, [flag, paths @ ..]) if flag == "--check-types" && !paths.is_empty() => check_files(paths, typecheck::check),
+        (None, [command, args @ ..]) if command == "fmt" => format_files(args),
+        (None, [flag, ..]) if flag.starts_with('-') && flag != "-" && !ended => {
+            eprintln!("pearsh: {}: invalid option", flag);
+            eprintln!("usage: pearsh [-elx] [--login] [--tokens] [--dialect=name] [-c command [name args...]] [file [args...]]");
+            eprintln!("       pearsh --lint file...");
+            eprintln!("       pearsh --check-types file...");
+            eprintln!("       pearsh fmt [--check] file...");
+            2
+        }
+        (None, [path, args @ ..]) => {
+            shell.set_args(path, args);
+            shell::run_script(path, &mut shell)
+        }
+        (None, []) if io::stdin().is_terminal() => shell::repl(&mut shell),
+        (None, []) => shell::run_lines(io::stdin().lines(), "stdin", &mut shell).unwrap_or(shell.status()),
+    };
+
+    // The logout file can't change the status, but it can cut itself short
+    // with `exit`.
+    if login {
+        if let Some(path) = shell::home_file(".pearsh_logout") {
+            shell::source_file(&path, &mut shell);
+        }
+    }
+
+    process::exit(status);
+}
@@ -0,0 +1,230 @@
+use std::{env, fs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::history::strftime;
+
+/// Used when `$PS1` isn't set.
+pub const DEFAULT_PS1: &str = "\\u@\\h:\\w\\$ ";
+
+/// Used when `$PS2` isn't set.
+pub const DEFAULT_PS2: &str = "> ";
+
+/// What a prompt can show about the last command.
+#[derive(Clone, Copy, Default)]
+pub struct LastCommand {
+    pub status: i32,
+    pub elapsed: Duration,
+}
+
+/// Renders the prompt from `ps1`, the value of `$PS1`, or the default if
+/// it isn't set. `reporttime` is the value of `$REPORTTIME`.
+///
+/// The default prompt is prefixed with the last command's status if it
+/// failed, and with how long it took if that reached `$REPORTTIME`.
+pub fn ps1(ps1: Option<&str>, reporttime: Option<&str>, last: &LastCommand) -> String {
+    if let Some(ps1) = ps1 {
+        return render(ps1, reporttime, last);
+    }
+
+    let mut prompt = String::new();
+    if last.status != 0 {
+        prompt.push_str(&format!("[{}] ", last.status));
+    }
+    if threshold(reporttime).is_some_and(|threshold| last.elapsed >= threshold) {
+        prompt.push_str(&format!("{} ", elapsed(last.elapsed)));
+    }
+    prompt + &render(DEFAULT_PS1, reporttime, last)
+}
+
+/// Renders the continuation prompt from `ps2`, the value of `$PS2`, or the
+/// default if it isn't set.
+pub fn ps2(ps2: Option<&str>, reporttime: Option<&str>, last: &LastCommand) -> String {
+    render(ps2.unwrap_or(DEFAULT_PS2), reporttime, last)
+}
+
+/// Expands the backslash escapes in a prompt string.
+///
+/// Supported escapes:
+/// .   \u  username             \h  hostname up to the first '.'
+/// .   \H  full hostname        \w  working directory, with $HOME as ~
+/// .   \W  basename of \w       \$  '#' for root, '$' otherwise
+/// .   \s  shell name           \v  shell version
+/// .   \?  last exit status     \E  how long the last command took
+/// .   \t  time, as 23:59:59    \T  time, as 11:59:59
+/// .   \@  time, as 11:59 PM    \A  time, as 23:59
+/// .   \d  date, as Tue May 26  \D{format}  time in a strftime format
+/// .   \n  newline              \e  escape, for color codes
+/// .   \\  backslash            \[ \]  delimit non-printing characters
+///
+/// When `reporttime`, the value of `$REPORTTIME`, is set, `\E` is empty
+/// unless the last command took at least that many seconds. Times are in
+/// UTC, as [`strftime`] gives them, and `\D{}` shows the time as `%X` does.
+/// Unrecognized escapes are left as-is.
+pub fn render(template: &str, reporttime: Option<&str>, last: &LastCommand) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    render_at(template, reporttime, last, now)
+}
+
+/// Renders `template` as [`render`] does, with `now`, in seconds since the
+/// epoch, as the current time.
+fn render_at(template: &str, reporttime: Option<&str>, last: &LastCommand, now: u64) -> String {
+    let mut prompt = String::new();
+    let mut iter = template.chars();
+
+    while let Some(c) = iter.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+
+        match iter.next() {
+            Some('u') => prompt.push_str(&username()),
+            Some('h') => prompt.push_str(hostname().split('.').next().unwrap_or_default()),
+            Some('H') => prompt.push_str(&hostname()),
+            Some('w') => prompt.push_str(&cwd()),
+            Some('W') => {
+                let cwd = cwd();
+                match cwd.as_str() {
+                    "/" | "~" => prompt.push_str(&cwd),
+                    _ => prompt.push_str(cwd.rsplit('/').next().unwrap_or_default()),
+                }
+            }
+            Some('$') => prompt.push(if username() == "root" { '#' } else { '$' }),
+            Some('s') => prompt.push_str(env!("CARGO_PKG_NAME")),
+            Some('v') => prompt.push_str(env!("CARGO_PKG_VERSION")),
+            Some('?') => prompt.push_str(&last.status.to_string()),
+            Some('E') => {
+                if threshold(reporttime).is_none_or(|threshold| last.elapsed >= threshold) {
+                    prompt.push_str(&elapsed(last.elapsed));
+                }
+            }
+            Some('t') => prompt.push_str(&strftime("%H:%M:%S", now)),
+            Some('T') => prompt.push_str(&strftime("%I:%M:%S", now)),
+            Some('@') => prompt.push_str(&strftime("%I:%M %p", now)),
+            Some('A') => prompt.push_str(&strftime("%H:%M", now)),
+            Some('d') => prompt.push_str(&strftime("%a %b %d", now)),
+            Some('D') => match iter.as_str().strip_prefix('{').and_then(|rest| rest.split_once('}')) {
+                Some((format, rest)) => {
+                    prompt.push_str(&strftime(if format.is_empty() { "%X" } else { format }, now));
+                    iter = rest.chars();
+                }
+                None => prompt.push_str("\\D"),
+            },
+            Some('n') => prompt.push('\n'),
+            Some('e') => prompt.push('\x1b'),
+            Some('\\') => prompt.push('\\'),
+            Some('[') | Some(']') => {}
+            Some(other) => {
+                prompt.push('\\');
+                prompt.push(other);
+            }
+            None => prompt.push('\\'),
+        }
+    }
+
+    prompt
+}
+
+/// The threshold `reporttime`, the value of `$REPORTTIME`, gives in seconds.
+fn threshold(reporttime: Option<&str>) -> Option<Duration> {
+    reporttime?.trim().parse().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+/// A duration in a short form: `1.25s`, `2m05s` or `1h02m`.
+fn elapsed(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{:.2}s", duration.as_secs_f64()),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+fn username() -> String {
+    env::var("USER").unwrap_or_default()
+}
+
+fn hostname() -> String {
+    env::var("HOSTNAME")
+        .or_else(|_| fs::read_to_string("/proc/sys/kernel/hostname"))
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+/// The working directory, with the home directory abbreviated to `~`.
+fn cwd() -> String {
+    let Ok(cwd) = env::current_dir() else {
+        return String::new();
+    };
+    let cwd = cwd.to_string_lossy().into_owned();
+
+    match env::var("HOME") {
+        Ok(home) if !home.is_empty() && home != "/" => match cwd.strip_prefix(&home) {
+            Some("") => "~".to_string(),
+            Some(rest) if rest.starts_with('/') => format!("~{}", rest),
+            _ => cwd,
+        },
+        _ => cwd,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2001-09-09 13:46:40 UTC, a Sunday.
+    const NOW: u64 = 1_000_043_200;
+
+    fn rendered(template: &str, reporttime: Option<&str>, status: i32, elapsed: u64) -> String {
+        let last = LastCommand { status, elapsed: Duration::from_secs(elapsed) };
+        render_at(template, reporttime, &last, NOW)
+    }
+
+    #[test]
+    fn escapes() {
+        for (template, expected) in [
+            ("plain > ", "plain > "),
+            ("\\s-\\v", concat!(env!("CARGO_PKG_NAME"), "-", env!("CARGO_PKG_VERSION"))),
+            ("[\\?]", "[1]"),
+            ("a\\nb\\\\c", "a\nb\\c"),
+            ("\\[\\e[1m\\]bold", "\x1b[1mbold"),
+            ("\\q \\D \\D{%Y", "\\q \\D \\D{%Y"),
+            ("trailing \\", "trailing \\"),
+        ] {
+            assert_eq!(rendered(template, None, 1, 0), expected, "{:?}", template);
+        }
+    }
+
+    #[test]
+    fn time_escapes() {
+        for (template, expected) in [
+            ("\\t", "13:46:40"),
+            ("\\T", "01:46:40"),
+            ("\\@", "01:46 PM"),
+            ("\\A", "13:46"),
+            ("\\d", "Sun Sep 09"),
+            ("[\\D{%Y-%m-%d}]", "[2001-09-09]"),
+            ("\\D{}", "13:46:40"),
+        ] {
+            assert_eq!(rendered(template, None, 0, 0), expected, "{:?}", template);
+        }
+    }
+
+    #[test]
+    fn elapsed_time() {
+        assert_eq!(rendered("\\E", None, 0, 3), "3.00s");
+        assert_eq!(rendered("\\E", None, 0, 125), "2m05s");
+        assert_eq!(rendered("\\E", None, 0, 3720), "1h02m");
+        assert_eq!(rendered("[\\E]", Some("5"), 0, 3), "[]");
+        assert_eq!(rendered("[\\E]", Some("5"), 0, 5), "[5.00s]");
+    }
+
+    #[test]
+    fn default_prompt_shows_failures() {
+        let last = LastCommand { status: 2, elapsed: Duration::from_secs(7) };
+        assert!(ps1(None, Some("5"), &last).starts_with("[2] 7.00s "));
+        assert_eq!(ps1(Some("$ "), Some("5"), &last), "$ ");
+        assert_eq!(ps2(None, None, &last), DEFAULT_PS2);
+    }
+}
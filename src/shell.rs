@@ -0,0 +1,1715 @@
+use std::{cell::RefCell, collections::{BTreeMap, BTreeSet}, env, ffi::OsString, fs::{self, File}, io::{self, BufRead, BufReader, IsTerminal, Read, Write}, iter, mem, ops::Range, path::{Path, PathBuf}, rc::Rc, time::Instant};
+
+use crate::complete;
+use crate::diagnostics::{self, Diagnostic, Location};
+use crate::dialect::{self, Dialect};
+use crate::editor::{EditMode, Editor};
+use crate::error::PearshError;
+use crate::highlight;
+use crate::hooks::ShellHook;
+use crate::history::History;
+use crate::keymap::{Action, Key};
+use crate::lexer::{self, Token, TokenKind};
+use crate::prompt::{self, LastCommand};
+use crate::value::{self, Type, Value};
+
+/// Writes a line to a shell's stdout or stderr, as `println!` and
+/// `eprintln!` do to the process's. Failed writes are ignored, since
+/// there's nowhere left to report them.
+macro_rules! outln {
+    ($streams:expr, $($arg:tt)*) => {{
+        let _ = writeln!($streams.stdout, $($arg)*);
+    }};
+}
+
+macro_rules! errln {
+    ($streams:expr, $($arg:tt)*) => {{
+        let _ = writeln!($streams.stderr, $($arg)*);
+    }};
+}
+
+/// Where a shell's commands write: the process's stdout and stderr, unless
+/// an embedder has redirected them.
+struct Streams {
+    stdout: Box<dyn Write>,
+    stderr: Box<dyn Write>,
+    redirected: bool,
+}
+
+impl Streams {
+    fn inherited() -> Streams {
+        Streams { stdout: Box::new(io::stdout()), stderr: Box::new(io::stderr()), redirected: false }
+    }
+}
+
+/// A writer into a buffer that outlives it, so that a shell's output can be
+/// read back after [`Shell::capture`] swaps the writer out again.
+#[derive(Clone, Default)]
+struct Buffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Prints a table of tokens: each one's byte span, kind and lexeme, colored
+/// by kind when stdout is a terminal. Control characters in lexemes are
+/// escaped so each token stays on one line.
+fn print_lex_results(tokens: &[Token], streams: &mut Streams) {
+    let colored = !streams.redirected && io::stdout().is_terminal();
+    for token in tokens {
+        let span = format!("{}..{}", token.span.start, token.span.end);
+        let kind = token.kind.name();
+        let lexeme: String = token.lexeme
+            .chars()
+            .map(|c| if c.is_control() { c.escape_default().to_string() } else { c.to_string() })
+            .collect();
+
+        match highlight::color(&token.kind) {
+            Some(color) if colored => {
+                outln!(streams, "{:<9} {}{:<12}{} {}{}{}", span, color, kind, highlight::RESET, color, lexeme, highlight::RESET);
+            }
+            _ => outln!(streams, "{:<9} {:<12} {}", span, kind, lexeme),
+        }
+    }
+}
+
+/// Commands the shell handles itself.
+pub const BUILTINS: &[&str] =
+    &[".", "bind", "cd", "exit", "false", "from-json", "history", "import", "set", "source", "to-json", "trap", "true"];
+
+/// What `trap` can set commands for: `DEBUG` runs before each command, and
+/// `ERR` after one fails.
+const TRAPS: &[&str] = &["DEBUG", "ERR"];
+
+/// Words that are part of a compound command rather than the name of a
+/// command to run.
+pub(crate) const RESERVED: &[&str] = &[
+    "!", "[[", "]]", "case", "do", "done", "elif", "else", "esac", "fi", "for", "function", "if", "in", "select",
+    "then", "time", "until", "while", "{", "}",
+];
+
+/// How many previous directories `cd` remembers.
+const DIR_HISTORY_SIZE: usize = 20;
+
+/// How deeply files can source each other before `source` gives up, so
+/// that a file sourcing itself doesn't overflow the stack.
+const MAX_SOURCE_DEPTH: usize = 100;
+
+/// If the line is an `exit [n]` command, returns the status to exit with.
+///
+/// The status is `n` truncated to 0-255, or `last`, the status of the last
+/// command, if no argument was given. A non-numeric argument exits with
+/// status 2, as in other shells.
+fn exit_builtin(words: &[&str], last: i32, streams: &mut Streams) -> Option<i32> {
+    match words {
+        ["exit"] => Some(last),
+        ["exit", arg] => match arg.parse::<i64>() {
+            Ok(n) => Some(n.rem_euclid(256) as i32),
+            Err(_) => {
+                errln!(streams, "pearsh: exit: {}: numeric argument required", arg);
+                Some(2)
+            }
+        },
+        ["exit", ..] => {
+            errln!(streams, "pearsh: exit: too many arguments");
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Options a shell starts with. Other than `dialect`, each is also a
+/// `set -o` option of the same name; emacs mode is the default when `vi`
+/// is off.
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// The dialect scripts are in, or `None` to go by each script's `#!`
+    /// line.
+    pub dialect: Option<Dialect>,
+    pub checkedarith: bool,
+    pub errexit: bool,
+    pub lexdebug: bool,
+    pub sharehistory: bool,
+    pub title: bool,
+    pub vi: bool,
+    pub xtrace: bool,
+}
+
+/// Runs commands that aren't builtins, for a shell that shouldn't look
+/// them up itself. Given a command's words, unquoted, returns its status.
+pub type CommandHandler = Box<dyn FnMut(&[&str]) -> i32>;
+
+/// Runs a command registered with [`Shell::register_command`]. Given the
+/// command's arguments, already checked against its parameters, returns
+/// its status.
+pub type NativeFn = Box<dyn FnMut(&[Value]) -> i32>;
+
+/// A registered command: the types of its parameters, and what it runs.
+struct NativeCommand {
+    params: Vec<Type>,
+    run: NativeFn,
+}
+
+/// How [`Shell::eval`] finished.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExitStatus {
+    /// The status of the last command, or the one given to `exit`.
+    pub code: i32,
+    /// Whether the input ran `exit`, which would have ended a script.
+    pub exited: bool,
+}
+
+/// What [`Shell::capture`] ran wrote, and how it finished.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl ExitStatus {
+    pub fn success(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// State that commands can change. Commands run in a shell with
+/// [`Shell::eval`] or [`execute`], or from a script or other input with
+/// [`run_script`], [`run_string`] and [`run_lines`].
+pub struct Shell {
+    editor: Editor,
+    /// Whether to show the running command, or the working directory
+    /// between commands, in the terminal's title.
+    title: bool,
+    /// Whether to print each command's tokens before running it.
+    lexdebug: bool,
+    /// Whether to exit as soon as a command fails, as `set -e` does in
+    /// other shells.
+    errexit: bool,
+    /// Whether to print each command to stderr before running it, as
+    /// `set -x` does in other shells.
+    xtrace: bool,
+    /// Whether ints out of range are an error, rather than wrapping around
+    /// as they do in bash.
+    checkedarith: bool,
+    /// The dialect given on the command line, if any.
+    dialect: Option<Dialect>,
+    /// Whether to write history as commands are entered and pick up what
+    /// other shells write, instead of saving it all at exit.
+    sharehistory: bool,
+    /// Previous working directories, most recent first.
+    dirs: Vec<PathBuf>,
+    /// Shell variables, set by assignments. The environment is read through
+    /// when a name isn't set here.
+    vars: BTreeMap<String, String>,
+    /// The types of variables declared with one, as in `int x = 5`.
+    /// Assignments to them are checked against it; other variables hold
+    /// any string.
+    types: BTreeMap<String, Type>,
+    /// Variables declared with `const`, which can't be assigned again.
+    constants: BTreeSet<String>,
+    /// Command history, which only interactive shells keep.
+    history: Option<History>,
+    /// Status and running time of the last command.
+    last: LastCommand,
+    /// Where the running command was read from: the script, `-c` string or
+    /// other input it's in last, after the files that sourced that one.
+    /// Empty for commands typed in at the prompt.
+    frames: Vec<Location>,
+    /// Commands set with `trap`, by the name of what they trap.
+    traps: BTreeMap<&'static str, String>,
+    /// Whether a trap is running, so that it doesn't set off traps itself.
+    in_trap: bool,
+    /// Runs commands instead of looking them up, if set.
+    handler: Option<CommandHandler>,
+    /// Commands registered by the embedder, by name.
+    commands: BTreeMap<String, NativeCommand>,
+    hooks: Vec<Box<dyn ShellHook>>,
+    streams: Streams,
+    /// Scratch space for [`execute`] to lex commands into.
+    tokens: Vec<Token>,
+}
+
+impl Shell {
+    /// Options that `set -o` and `set +o` can turn on and off.
+    pub const OPTIONS: &'static [&'static str] =
+        &["checkedarith", "emacs", "errexit", "lexdebug", "sharehistory", "strict", "title", "vi", "xtrace"];
+
+    pub fn new(options: Options) -> Shell {
+        let mut editor = Editor::new();
+        if options.vi {
+            editor.set_mode(EditMode::Vi);
+        }
+        Shell {
+            editor,
+            title: options.title,
+            lexdebug: options.lexdebug,
+            errexit: options.errexit,
+            xtrace: options.xtrace,
+            checkedarith: options.checkedarith,
+            dialect: options.dialect,
+            sharehistory: options.sharehistory,
+            dirs: Vec::new(),
+            vars: BTreeMap::new(),
+            types: BTreeMap::new(),
+            constants: BTreeSet::new(),
+            history: None,
+            last: LastCommand::default(),
+            frames: Vec::new(),
+            traps: BTreeMap::new(),
+            in_trap: false,
+            handler: None,
+            commands: BTreeMap::new(),
+            hooks: Vec::new(),
+            streams: Streams::inherited(),
+            tokens: Vec::new(),
+        }
+    }
+
+    /// Runs `source` as a script would run, returning how it finished.
+    ///
+    /// Commands that fail, like ones that aren't found, report why on
+    /// stderr and show up in the status. Input that can't be lexed is an
+    /// error instead, and none of it runs. Input with constructs pearsh
+    /// can't run yet, like `if` or bash's `[[`, doesn't run either, failing
+    /// with status 2 as a script would.
+    pub fn eval(&mut self, source: &str) -> Result<ExitStatus, PearshError> {
+        lexer::lex(&lexer::strip_comments(source))?;
+        if !runs_as(source, "eval", self) {
+            self.last.status = 2;
+            return Ok(ExitStatus { code: 2, exited: false });
+        }
+        let exit = run_string(source, "eval", self);
+        Ok(ExitStatus { code: exit.unwrap_or(self.last.status), exited: exit.is_some() })
+    }
+
+    /// Runs `source` as [`eval`](Self::eval) does, but collects what it
+    /// writes to stdout and stderr instead of letting it through. Errors,
+    /// like commands that aren't found, end up in `stderr`, uncolored.
+    pub fn capture(&mut self, source: &str) -> Result<Output, PearshError> {
+        let (stdout, stderr) = (Buffer::default(), Buffer::default());
+        let streams = Streams { stdout: Box::new(stdout.clone()), stderr: Box::new(stderr.clone()), redirected: true };
+        let inherited = mem::replace(&mut self.streams, streams);
+        let status = self.eval(source);
+        self.streams = inherited;
+
+        Ok(Output { status: status?, stdout: stdout.0.take(), stderr: stderr.0.take() })
+    }
+
+    /// Sends what commands write to `stdout` and `stderr` rather than the
+    /// process's, as it's written, for embedders that stream it somewhere.
+    pub fn set_output(&mut self, stdout: impl Write + 'static, stderr: impl Write + 'static) {
+        self.streams = Streams { stdout: Box::new(stdout), stderr: Box::new(stderr), redirected: true };
+    }
+
+    /// The value of a shell variable, or of the environment variable of
+    /// that name if there's no shell variable.
+    pub fn get_var(&self, name: &str) -> Option<String> {
+        self.vars.get(name).cloned().or_else(|| env::var(name).ok())
+    }
+
+    /// Sets a shell variable, as `name=value` does, except that `value`
+    /// isn't checked against the variable's declared type, and constants
+    /// can be set too. Nothing is exported to the environment.
+    pub fn set_var(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_string(), value.to_string());
+    }
+
+    /// The value of a variable as its declared type, or as a string if it
+    /// wasn't declared with one.
+    pub fn get_value(&self, name: &str) -> Option<Value> {
+        let value = self.get_var(name)?;
+        match self.types.get(name) {
+            Some(ty) => ty.parse(&value),
+            None => Some(Value::Str(value)),
+        }
+    }
+
+    /// The type a variable was declared with, if any.
+    pub fn var_type(&self, name: &str) -> Option<Type> {
+        self.types.get(name).copied()
+    }
+
+    /// Whether a variable was declared with `const`.
+    pub fn is_constant(&self, name: &str) -> bool {
+        self.constants.contains(name)
+    }
+
+    /// Sets a variable, as `name=value` does, checking `value` against its
+    /// declared type. The error points at `span`, where the value is.
+    fn assign(&mut self, name: &str, value: &str, span: Range<usize>) -> Result<(), PearshError> {
+        if self.constants.contains(name) {
+            return Err(PearshError::ConstantAssignment(name.to_string(), span));
+        }
+        let value = match self.types.get(name) {
+            Some(&ty) => match value::read_value(name, ty, value, self.checkedarith) {
+                Ok(value) => value.to_string(),
+                Err(message) => return Err(PearshError::TypeMismatch(message, span)),
+            },
+            None => value.to_string(),
+        };
+        self.set_var(name, &value);
+        Ok(())
+    }
+
+    /// Sets the positional parameters: `$0` to `name`, `$1` onwards to
+    /// `args`, and `$#` to how many of those there are. Any left over from
+    /// before are unset.
+    pub fn set_args(&mut self, name: &str, args: &[impl AsRef<str>]) {
+        self.vars.retain(|var, _| !is_positional(var));
+        self.set_var("0", name);
+        for (i, arg) in args.iter().enumerate() {
+            self.set_var(&(i + 1).to_string(), arg.as_ref());
+        }
+        self.set_var("#", &args.len().to_string());
+    }
+
+    /// Hands commands that aren't builtins to `handler` rather than looking
+    /// for them on `$PATH`, so that the shell never touches the host's
+    /// programs. Embedders use this to decide what commands exist.
+    pub fn set_command_handler(&mut self, handler: impl FnMut(&[&str]) -> i32 + 'static) {
+        self.handler = Some(Box::new(handler));
+    }
+
+    /// Registers `run` as the command `name`, taking an argument of each of
+    /// the types in `params`. Registered commands take the place of any
+    /// program of the same name, but not of builtins.
+    ///
+    /// The shell checks the arguments before `run` sees them: given the
+    /// wrong number of arguments, or one that isn't of its parameter's type,
+    /// the command fails with status 2 and an error pointing at the problem,
+    /// as in
+    ///
+    /// .   error[E005]: resize: expected an int, found `wide`
+    /// .    --> eval:1:8
+    /// .     |
+    /// .   1 | resize wide 600
+    /// .     |        ^^^^
+    pub fn register_command(&mut self, name: &str, params: &[Type], run: impl FnMut(&[Value]) -> i32 + 'static) {
+        let command = NativeCommand { params: params.to_vec(), run: Box::new(run) };
+        self.commands.insert(name.to_string(), command);
+    }
+
+    /// Registers a hook, to be called after any registered before it.
+    pub fn add_hook(&mut self, hook: impl ShellHook + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Reports an error in `source` to the hooks, and then on the shell's
+    /// stderr.
+    fn report(&mut self, err: &PearshError, source: &str) {
+        for hook in &mut self.hooks {
+            hook.on_error(err);
+        }
+        let colored = !self.streams.redirected && io::stderr().is_terminal();
+        let _ = self.streams.stderr.write_all(err.render(source, &self.frames, colored).as_bytes());
+    }
+
+    /// Reports each of `diagnostics`, for constructs in `source` that can't
+    /// run, as [`report`](Self::report) does. `source` is the whole of the
+    /// file `name`, rather than a command in it.
+    fn report_unsupported(&mut self, diagnostics: Vec<Diagnostic>, source: &str, name: &str) {
+        self.frames.push(Location { name: name.to_string(), line: 1 });
+        for diagnostic in diagnostics {
+            self.report(&PearshError::Unsupported(diagnostic), source);
+        }
+        self.frames.pop();
+    }
+
+    /// The exit status of the last command.
+    pub fn status(&self) -> i32 {
+        self.last.status
+    }
+
+    pub fn option(&self, name: &str) -> bool {
+        match name {
+            "emacs" => self.editor.mode() == EditMode::Emacs,
+            "vi" => self.editor.mode() == EditMode::Vi,
+            "title" => self.title,
+            "lexdebug" => self.lexdebug,
+            "errexit" | "strict" => self.errexit,
+            "sharehistory" => self.sharehistory,
+            "xtrace" => self.xtrace,
+            "checkedarith" => self.checkedarith,
+            _ => false,
+        }
+    }
+
+    /// Turns an option on or off. Line editing can't be turned off, so
+    /// turning off `vi` switches to emacs mode, and turning off `emacs`
+    /// does nothing.
+    ///
+    /// `strict` turns on or off every option that strict mode takes. For
+    /// now that's only `errexit`: `nounset`, `pipefail` and `failglob` will
+    /// join it once there are expansions, pipelines and globs for them to
+    /// act on.
+    pub fn set_option(&mut self, name: &str, on: bool) {
+        match (name, on) {
+            ("emacs", true) | ("vi", false) => self.editor.set_mode(EditMode::Emacs),
+            ("vi", true) => self.editor.set_mode(EditMode::Vi),
+            ("title", _) => self.title = on,
+            ("lexdebug", _) => self.lexdebug = on,
+            ("errexit" | "strict", _) => self.errexit = on,
+            ("sharehistory", _) => self.sharehistory = on,
+            ("xtrace", _) => self.xtrace = on,
+            ("checkedarith", _) => self.checkedarith = on,
+            _ => {}
+        }
+    }
+}
+
+/// A copy of what commands can change in a [`Shell`], taken so that
+/// commands can be run speculatively and their effects undone: shell
+/// variables, options, traps, the working directory and the directory
+/// history.
+///
+/// There are no functions or aliases yet; they'll be captured too once
+/// there are. Whatever commands do outside the shell, like writing files or
+/// setting environment variables other than `$PWD` and `$OLDPWD`, isn't
+/// undone.
+#[derive(Clone, Debug)]
+pub struct ShellState {
+    vars: BTreeMap<String, String>,
+    types: BTreeMap<String, Type>,
+    constants: BTreeSet<String>,
+    options: Vec<(&'static str, bool)>,
+    dialect: Option<Dialect>,
+    traps: BTreeMap<&'static str, String>,
+    cwd: Option<PathBuf>,
+    pwd: Option<OsString>,
+    oldpwd: Option<OsString>,
+    dirs: Vec<PathBuf>,
+}
+
+impl ShellState {
+    pub fn snapshot(shell: &Shell) -> ShellState {
+        ShellState {
+            vars: shell.vars.clone(),
+            types: shell.types.clone(),
+            constants: shell.constants.clone(),
+            options: Shell::OPTIONS.iter().map(|&name| (name, shell.option(name))).collect(),
+            dialect: shell.dialect,
+            traps: shell.traps.clone(),
+            cwd: env::current_dir().ok(),
+            pwd: env::var_os("PWD"),
+            oldpwd: env::var_os("OLDPWD"),
+            dirs: shell.dirs.clone(),
+        }
+    }
+
+    /// Puts `shell` back the way it was when the snapshot was taken. The
+    /// rest is restored even if the working directory can't be, as when
+    /// it's since been removed, in which case that's the error returned.
+    pub fn restore(&self, shell: &mut Shell) -> io::Result<()> {
+        shell.vars = self.vars.clone();
+        shell.types = self.types.clone();
+        shell.constants = self.constants.clone();
+        for &(name, on) in &self.options {
+            shell.set_option(name, on);
+        }
+        shell.dialect = self.dialect;
+        shell.traps = self.traps.clone();
+        shell.dirs = self.dirs.clone();
+        shell.editor.set_recent_dirs(shell.dirs.iter().map(|dir| dir.to_string_lossy().into_owned()).collect());
+
+        for (name, value) in [("PWD", &self.pwd), ("OLDPWD", &self.oldpwd)] {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+        match &self.cwd {
+            Some(cwd) => env::set_current_dir(cwd),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for Shell {
+    fn default() -> Shell {
+        Shell::new(Options::default())
+    }
+}
+
+/// Options that `set` takes as single letters, as in `set -e`, with the
+/// `set -o` option each one stands for.
+const SHORT_OPTIONS: &[(char, &str)] = &[('e', "errexit"), ('x', "xtrace")];
+
+/// The options a flag like `-e` turns on, or `+e` turns off, along with
+/// whether it turns them on. Letters can be combined, as in `-ex`.
+fn short_options(flag: &str) -> Option<(Vec<&'static str>, bool)> {
+    let (letters, on) = match (flag.strip_prefix('-'), flag.strip_prefix('+')) {
+        (Some(letters), _) => (letters, true),
+        (_, Some(letters)) => (letters, false),
+        _ => return None,
+    };
+    let names: Option<Vec<&str>> = letters
+        .chars()
+        .map(|letter| SHORT_OPTIONS.iter().find(|(short, _)| *short == letter).map(|(_, name)| *name))
+        .collect();
+    names.filter(|names| !names.is_empty()).map(|names| (names, on))
+}
+
+/// Handles `set -o [option]` and `set +o [option]`, and the single-letter
+/// flags in [`SHORT_OPTIONS`], like `set -e` and `set +e`.
+fn set_builtin(words: &[&str], shell: &mut Shell) -> i32 {
+    match words {
+        ["set", "-o"] => {
+            for name in Shell::OPTIONS {
+                outln!(shell.streams, "{:<15}{}", name, if shell.option(name) { "on" } else { "off" });
+            }
+        }
+        ["set", "+o"] => {
+            for name in Shell::OPTIONS {
+                outln!(shell.streams, "set {}o {}", if shell.option(name) { '-' } else { '+' }, name);
+            }
+        }
+        ["set", flag @ ("-o" | "+o"), name] if Shell::OPTIONS.contains(name) => {
+            shell.set_option(name, *flag == "-o");
+        }
+        ["set", "-o" | "+o", name] => {
+            errln!(shell.streams, "pearsh: set: {}: invalid option name", name);
+            return 1;
+        }
+        ["set", flags @ ..] if !flags.is_empty() && flags.iter().all(|flag| short_options(flag).is_some()) => {
+            for (names, on) in flags.iter().filter_map(|flag| short_options(flag)) {
+                for name in names {
+                    shell.set_option(name, on);
+                }
+            }
+        }
+        _ => {
+            errln!(shell.streams, "pearsh: set: usage: set [-ex|+ex] [-o|+o [option]]");
+            return 2;
+        }
+    }
+    0
+}
+
+/// Handles `cd [dir]`, `cd -` and `cd -N`.
+///
+/// With no argument, changes to `$HOME`. `cd -N` changes to the Nth most
+/// recent previous directory, so `cd -` is `cd -1`, and prints where it went.
+fn cd_builtin(words: &[&str], shell: &mut Shell) -> i32 {
+    let target = match words {
+        [_] => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home),
+            None => {
+                errln!(shell.streams, "pearsh: cd: HOME not set");
+                return 1;
+            }
+        },
+        [_, "-"] => match shell.dirs.first() {
+            Some(dir) => dir.clone(),
+            None => {
+                errln!(shell.streams, "pearsh: cd: no previous directory");
+                return 1;
+            }
+        },
+        [_, arg] if arg.len() > 1 && arg.starts_with('-') => {
+            match arg[1..].parse::<usize>().ok().and_then(|n| shell.dirs.get(n.checked_sub(1)?)) {
+                Some(dir) => dir.clone(),
+                None => {
+                    errln!(shell.streams, "pearsh: cd: {}: no such entry in directory history", arg);
+                    return 1;
+                }
+            }
+        }
+        [_, dir] => PathBuf::from(lexer::unquote(dir)),
+        _ => {
+            errln!(shell.streams, "pearsh: cd: too many arguments");
+            return 2;
+        }
+    };
+
+    let previous = env::current_dir().ok();
+    if let Err(err) = env::set_current_dir(&target) {
+        errln!(shell.streams, "pearsh: cd: {}: {}", target.display(), err);
+        return 1;
+    }
+    if words[1..].first().is_some_and(|arg| arg.starts_with('-')) {
+        outln!(shell.streams, "{}", target.display());
+    }
+
+    let current = env::current_dir().unwrap_or(target);
+    env::set_var("PWD", &current);
+    if let Some(previous) = &previous {
+        for hook in &mut shell.hooks {
+            hook.on_cd(previous, &current);
+        }
+    }
+    if let Some(previous) = previous {
+        env::set_var("OLDPWD", &previous);
+        shell.dirs.retain(|dir| *dir != previous && *dir != current);
+        shell.dirs.insert(0, previous);
+        shell.dirs.truncate(DIR_HISTORY_SIZE);
+    }
+    shell.editor.set_recent_dirs(shell.dirs.iter().map(|dir| dir.to_string_lossy().into_owned()).collect());
+    0
+}
+
+/// Handles `trap [-p] [action name...]`. `trap action name...` sets the
+/// command to run for each name, `trap - name...` unsets it, and `trap` or
+/// `trap -p [name...]` lists commands in a form that can be run to set them
+/// again. Only the pseudo-signals in [`TRAPS`] can be trapped.
+fn trap_builtin(words: &[&str], shell: &mut Shell) -> i32 {
+    let args: Vec<String> = words[1..].iter().map(|arg| lexer::unquote(arg)).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let (action, names) = match args.as_slice() {
+        [] | ["-p"] => (None, TRAPS),
+        ["-p", names @ ..] => (None, names),
+        ["--", action, names @ ..] | [action, names @ ..] if !names.is_empty() => (Some(*action), names),
+        // A lone name resets it, as in other shells.
+        [name] => (Some("-"), std::slice::from_ref(name)),
+        _ => {
+            errln!(shell.streams, "pearsh: trap: usage: trap [-p] [action name...]");
+            return 2;
+        }
+    };
+
+    let mut status = 0;
+    for name in names {
+        let Some(&name) = TRAPS.iter().find(|trap| *trap == name) else {
+            errln!(shell.streams, "pearsh: trap: {}: only DEBUG and ERR can be trapped", name);
+            status = 1;
+            continue;
+        };
+        match action {
+            None => {
+                if let Some(command) = shell.traps.get(name) {
+                    outln!(shell.streams, "trap -- '{}' {}", command.replace('\'', "'\\''"), name);
+                }
+            }
+            Some("-") => {
+                shell.traps.remove(name);
+            }
+            Some(command) => {
+                shell.traps.insert(name, command.to_string());
+            }
+        }
+    }
+    status
+}
+
+/// Runs the command trapping `name`, if one is set and no trap is running
+/// already. `$?` is left as it was. Returns the status to exit with if the
+/// trap asked the shell to exit.
+fn run_trap(name: &str, shell: &mut Shell) -> Option<i32> {
+    if shell.in_trap {
+        return None;
+    }
+    let command = shell.traps.get(name)?.clone();
+
+    let last = shell.last;
+    shell.in_trap = true;
+    let exit = run_string(&command, &format!("{} trap", name), shell);
+    shell.in_trap = false;
+    shell.last = last;
+    exit
+}
+
+/// Handles `history [n]`, which lists the last `n` commands, or all of them.
+fn history_builtin(words: &[&str], shell: &mut Shell) -> i32 {
+    let count = match words {
+        [_] => None,
+        [_, n] => match n.parse() {
+            Ok(n) => Some(n),
+            Err(_) => {
+                errln!(shell.streams, "pearsh: history: {}: numeric argument required", n);
+                return 2;
+            }
+        },
+        _ => {
+            errln!(shell.streams, "pearsh: history: too many arguments");
+            return 2;
+        }
+    };
+
+    let format = shell.get_var("HISTTIMEFORMAT");
+    if let Some(history) = &shell.history {
+        let _ = history.print(count, format.as_deref(), &mut shell.streams.stdout);
+    }
+    0
+}
+
+/// Handles `from-json name [json]`, which reads JSON, from the argument or
+/// else from stdin, and declares `name` with its value, as `let` would.
+/// Stdin is only read if it isn't the terminal, where reading it would
+/// leave the shell waiting for input no one meant to type.
+fn from_json_builtin(words: &[&str], spans: &[Range<usize>], input: &str, shell: &mut Shell) -> i32 {
+    let (name, json) = match words {
+        [_, _] if io::stdin().is_terminal() => {
+            errln!(shell.streams, "pearsh: from-json: give the JSON as an argument, or pipe it in");
+            return 2;
+        }
+        [_, name] => {
+            let mut json = String::new();
+            if let Err(err) = io::stdin().read_to_string(&mut json) {
+                errln!(shell.streams, "pearsh: from-json: {}", err);
+                return 1;
+            }
+            (*name, json)
+        }
+        [_, name, json] => (*name, lexer::unquote(json)),
+        _ => {
+            errln!(shell.streams, "pearsh: from-json: usage: from-json name [json]");
+            return 2;
+        }
+    };
+    if lexer::variable_name(name) != Some(name) {
+        errln!(shell.streams, "pearsh: from-json: `{}`: not a valid variable name", name);
+        return 2;
+    }
+
+    match Value::parse_json(&json) {
+        Ok(value) => define(name, Some(value.type_of()), false, &value.to_string(), spans[1].clone(), input, shell),
+        Err(err) => {
+            errln!(shell.streams, "pearsh: from-json: invalid JSON at byte {}: {}", err.span.start, err.message);
+            1
+        }
+    }
+}
+
+/// Handles `to-json name`, which prints the value of `name` as JSON.
+fn to_json_builtin(words: &[&str], shell: &mut Shell) -> i32 {
+    let [_, name] = words else {
+        errln!(shell.streams, "pearsh: to-json: usage: to-json name");
+        return 2;
+    };
+    let name = lexer::unquote(name);
+    match shell.get_value(&name) {
+        Some(value) => {
+            outln!(shell.streams, "{}", value.to_json());
+            0
+        }
+        None => {
+            errln!(shell.streams, "pearsh: to-json: {}: not set", name);
+            1
+        }
+    }
+}
+
+/// Handles `bind [-m keymap] [-lp] ['"keyseq": action-name' ...]`.
+///
+/// `-l` lists the action names, `-p` lists the keymap's bindings, and each
+/// binding argument rebinds a key in the keymap.
+fn bind_builtin(words: &[&str], editor: &mut Editor, streams: &mut Streams) -> i32 {
+    let args: Vec<String> = words[1..].iter().map(|arg| lexer::unquote(arg)).collect();
+
+    let mut status = 0;
+    let mut name = None;
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        if arg == "-m" {
+            match args.next() {
+                Some(keymap) if editor.keymap_mut(Some(keymap)).is_some() => name = Some(keymap),
+                Some(keymap) => {
+                    errln!(streams, "pearsh: bind: {}: invalid keymap name", keymap);
+                    return 1;
+                }
+                None => {
+                    errln!(streams, "pearsh: bind: -m: option requires an argument");
+                    return 2;
+                }
+            }
+            continue;
+        }
+
+        let keymap = editor.keymap_mut(name).expect("-m only accepts valid keymap names");
+        match arg {
+            "-l" => Action::names().for_each(|name| outln!(streams, "{}", name)),
+            "-p" => {
+                for (key, action) in keymap.bindings() {
+                    if let Some(seq) = key.sequence() {
+                        outln!(streams, "\"{}\": {}", seq, action.name());
+                    }
+                }
+            }
+            "-x" => {
+                errln!(streams, "pearsh: bind: -x: binding keys to commands isn't supported yet");
+                status = 1;
+            }
+            arg if arg.starts_with('-') => {
+                errln!(streams, "pearsh: bind: {}: invalid option", arg);
+                errln!(streams, "bind: usage: bind [-lp] [-m keymap] ['\"keyseq\": action-name' ...]");
+                return 2;
+            }
+            binding => {
+                let Some((seq, action)) = binding
+                    .strip_prefix('"')
+                    .and_then(|rest| rest.rsplit_once("\":"))
+                else {
+                    errln!(streams, "pearsh: bind: {}: expected '\"keyseq\": action-name'", binding);
+                    status = 1;
+                    continue;
+                };
+                match (Key::parse(seq), Action::from_name(action.trim())) {
+                    (Some(key), Some(action)) => keymap.bind(key, action),
+                    (None, _) => {
+                        errln!(streams, "pearsh: bind: \"{}\": invalid key sequence", seq);
+                        status = 1;
+                    }
+                    (_, None) => {
+                        errln!(streams, "pearsh: bind: {}: unknown action name", action.trim());
+                        status = 1;
+                    }
+                }
+            }
+        }
+    }
+    status
+}
+
+/// Sets the terminal's title, leaving out any control characters.
+fn set_title(title: &str) {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    print!("\x1b]0;{}\x07", title);
+}
+
+/// Reads a complete command into `input`, replacing what was there,
+/// prompting with `$PS2` for as many continuation lines as it takes, and
+/// records it in the history as one entry. Stops early at the end of input,
+/// and returns `false` if there was nothing left to read.
+fn read_command(shell: &mut Shell, input: &mut String) -> io::Result<bool> {
+    input.clear();
+    let ps2 = shell.get_var("PS2");
+    let reporttime = shell.get_var("REPORTTIME");
+    let control = shell.get_var("HISTCONTROL").unwrap_or_default();
+    let ignore = shell.get_var("HISTIGNORE").unwrap_or_default();
+    let mut prompt = prompt::ps1(shell.get_var("PS1").as_deref(), reporttime.as_deref(), &shell.last);
+    load_history(shell);
+    let Shell { editor, history, last, vars, .. } = shell;
+    editor.set_variables(vars.keys().filter(|name| lexer::variable_name(name) == Some(name)).cloned().collect());
+    let history = history.as_mut().expect("the history was just loaded");
+    let mut line = String::new();
+    let mut continuation = lexer::Continuation::default();
+
+    loop {
+        line.clear();
+        if editor.read_line(&prompt, history.entries(), &mut line)? == 0 {
+            history.push(input, &control, &ignore);
+            return Ok(!input.is_empty());
+        }
+        input.push_str(&line);
+        continuation.push(&line);
+
+        if !continuation.is_incomplete() {
+            history.push(input, &control, &ignore);
+            return Ok(true);
+        }
+        prompt = prompt::ps2(ps2.as_deref(), reporttime.as_deref(), last);
+    }
+}
+
+/// Loads the shell's history from `$HISTFILE`, if it hasn't been already,
+/// and keeps as much of it as `$HISTSIZE` and `$HISTFILESIZE` now say to.
+fn load_history(shell: &mut Shell) {
+    let size = shell.get_var("HISTSIZE");
+    let file_size = shell.get_var("HISTFILESIZE");
+    let file = shell.get_var("HISTFILE");
+    let history = shell.history.get_or_insert_with(|| History::load(file.as_deref()));
+    history.set_limits(size.as_deref(), file_size.as_deref());
+}
+
+/// How many consecutive EOFs an interactive shell ignores before exiting.
+///
+/// Like bash, this is `$IGNOREEOF`, or 10 if it's set to something that
+/// isn't a number.
+fn ignoreeof(shell: &Shell) -> usize {
+    match shell.get_var("IGNOREEOF") {
+        Some(count) => count.trim().parse().unwrap_or(10),
+        None => 0,
+    }
+}
+
+/// Runs one complete command, recording its status. Returns the status to
+/// exit with if it asked the shell to exit.
+pub fn execute(input: &str, shell: &mut Shell) -> Option<i32> {
+    match try_execute(input, shell) {
+        Ok(exit) => exit,
+        Err(err) => {
+            shell.report(&err, input);
+            shell.last.status = err.status();
+            None
+        }
+    }
+}
+
+/// Runs one complete command, as [`execute`] does, unless it doesn't lex or
+/// uses a construct pearsh can't run yet, in which case none of it runs.
+fn try_execute(input: &str, shell: &mut Shell) -> Result<Option<i32>, PearshError> {
+    // The shell keeps one token buffer to lex each command into. A command
+    // run from inside this one, like a trap, gets a buffer of its own.
+    let mut tokens = mem::take(&mut shell.tokens);
+    let exit = execute_tokens(input, &mut tokens, shell);
+    shell.tokens = tokens;
+    exit
+}
+
+fn execute_tokens(input: &str, tokens: &mut Vec<Token>, shell: &mut Shell) -> Result<Option<i32>, PearshError> {
+    // Comments are blanked out rather than removed, so spans into what's
+    // lexed are spans into `input` too.
+    let stripped;
+    let source = match input.contains('#') {
+        true => {
+            stripped = lexer::strip_comments(input);
+            &stripped
+        }
+        false => input,
+    };
+    let lexed = lexer::lex_into(source, tokens);
+    if shell.lexdebug {
+        print_lex_results(tokens, &mut shell.streams);
+    }
+    lexed?;
+    if let Some(diagnostic) = dialect::unimplemented(source, tokens).into_iter().next() {
+        return Err(PearshError::Unsupported(diagnostic));
+    }
+
+    // Commands separated by `;` or a newline run one after the other. One
+    // after `&&` only runs if the one before it succeeded, and one after
+    // `||` only if it failed.
+    let mut skip = false;
+    for command in tokens.split_inclusive(|token| is_separator(token.kind)) {
+        let (command, operator) = match command.split_last() {
+            Some((last, command)) if is_separator(last.kind) => (command, Some(last.kind)),
+            _ => (command, None),
+        };
+        let tested = matches!(operator, Some(TokenKind::LogicalAnd | TokenKind::LogicalOr));
+        if !skip {
+            if let Some(status) = run_command(input, command, !tested, shell) {
+                return Ok(Some(status));
+            }
+        }
+        skip = match operator {
+            Some(TokenKind::LogicalAnd) => shell.last.status != 0,
+            Some(TokenKind::LogicalOr) => shell.last.status == 0,
+            _ => false,
+        };
+    }
+    Ok(None)
+}
+
+/// Whether a token of this kind ends one command and starts the next.
+fn is_separator(kind: TokenKind) -> bool {
+    matches!(kind, TokenKind::Newline | TokenKind::Semicolon | TokenKind::LogicalAnd | TokenKind::LogicalOr)
+}
+
+/// Runs the simple command made up of `tokens`, recording its status
+/// unless it's empty. Returns the status to exit with if it asked the shell
+/// to exit.
+///
+/// A failure is an error, which sets off the ERR trap and, with `errexit`
+/// on, exits, if the command is `checked`. Commands followed by `&&` or
+/// `||` are being tested instead, so their failures aren't.
+fn run_command(input: &str, tokens: &[Token], checked: bool, shell: &mut Shell) -> Option<i32> {
+    let spans = lexer::words(tokens);
+    let words: Vec<&str> = spans.iter().map(|span| &input[span.clone()]).collect();
+    let (Some(first), Some(last)) = (spans.first(), spans.last()) else {
+        return None;
+    };
+    let command = &input[first.start..last.end];
+    if let Some(status) = run_trap("DEBUG", shell) {
+        return Some(status);
+    }
+    if shell.xtrace {
+        let prefix = shell.get_var("PS4").unwrap_or_else(|| "+ ".to_string());
+        let args: Vec<String> = words.iter().map(|word| lexer::unquote(word)).collect();
+        errln!(shell.streams, "{}{}", prefix, args.join(" "));
+    }
+    for hook in &mut shell.hooks {
+        hook.on_command_start(command);
+    }
+    let start = Instant::now();
+
+    // A type keyword starting a command declares a variable, and `const`
+    // before one, or in place of one, declares a constant.
+    let keyword = |i: usize| {
+        let span = spans.get(i)?;
+        tokens.iter().find(|token| token.span == *span).map(|token| token.kind)
+    };
+    let declared = match (keyword(0), keyword(1)) {
+        (Some(TokenKind::Const), second) => Some((true, second.and_then(Type::from_token))),
+        (first, _) => first.and_then(Type::from_token).map(|ty| (false, Some(ty))),
+    };
+    // A bool variable run as a command succeeds if it's true, and a
+    // declaration's status is the status of setting its variable.
+    let status = match declared {
+        Some((constant, ty)) => Some(declare(constant, ty, &words, &spans, input, shell)),
+        None => words.first().and_then(|word| lexer::expansion(word)).and_then(|name| shell.get_value(name)?.status()),
+    };
+    shell.last.status = match (words[0], status) {
+        (_, Some(status)) => status,
+        ("exit", _) => match exit_builtin(&words, shell.last.status, &mut shell.streams) {
+            Some(status) => return Some(status),
+            None => 2,
+        },
+        ("set", _) => set_builtin(&words, shell),
+        ("cd", _) => cd_builtin(&words, shell),
+        ("history", _) => history_builtin(&words, shell),
+        ("bind", _) => bind_builtin(&words, &mut shell.editor, &mut shell.streams),
+        ("trap", _) => trap_builtin(&words, shell),
+        ("from-json", _) => from_json_builtin(&words, &spans, input, shell),
+        ("to-json", _) => to_json_builtin(&words, shell),
+        ("true", _) => 0,
+        ("false", _) => 1,
+        (_, _) if keyword(0) == Some(TokenKind::Let) => declare_literal(&words, &spans, input, shell),
+        (_, _) if words.iter().all(|word| lexer::assignment(word).is_some()) => {
+            let mut status = 0;
+            for (word, span) in words.iter().zip(&spans) {
+                if let Some((name, value)) = lexer::assignment(word) {
+                    let unquoted = lexer::unquote(value);
+                    if let Err(err) = shell.assign(name, &unquoted, span.end - value.len()..span.end) {
+                        shell.report(&err, input);
+                        status = err.status();
+                    }
+                }
+            }
+            status
+        }
+        ("." | "source", _) => match source_builtin(&words, shell) {
+            Some(status) => return Some(status),
+            None => shell.last.status,
+        },
+        ("import", _) => match import_builtin(&words, shell) {
+            Some(status) => return Some(status),
+            None => shell.last.status,
+        },
+        (name, _) if shell.commands.contains_key(name) => {
+            let command = shell.commands.get_mut(name).expect("the command is registered");
+            match native_args(name, &command.params, input, &spans, shell.checkedarith) {
+                Ok(args) => (command.run)(&args),
+                Err(err) => {
+                    shell.report(&err, input);
+                    err.status()
+                }
+            }
+        }
+        (name, _) if is_command_name(name) => match &mut shell.handler {
+            Some(handler) => {
+                let args: Vec<String> = words.iter().map(|word| lexer::unquote(word)).collect();
+                handler(&args.iter().map(String::as_str).collect::<Vec<_>>())
+            }
+            // Without a handler, there's nothing to run a program found on
+            // `$PATH` with, since pearsh can't start processes yet.
+            None => {
+                let err = match find_command(name, spans[0].clone()) {
+                    Ok(path) => PearshError::Unsupported(
+                        Diagnostic::error(
+                            diagnostics::UNSUPPORTED_CONSTRUCT,
+                            format!("{}: can't execute external commands yet", name),
+                            spans[0].clone(),
+                        )
+                        .note(format!("found {}, but only builtins run for now", path.display())),
+                    ),
+                    Err(err) => err,
+                };
+                shell.report(&err, input);
+                err.status()
+            }
+        },
+        (name, _) => {
+            let err = PearshError::Unsupported(unrunnable(name, spans[0].clone(), shell));
+            shell.report(&err, input);
+            err.status()
+        }
+    };
+
+    let elapsed = start.elapsed();
+    for hook in &mut shell.hooks {
+        hook.on_command_end(command, shell.last.status, elapsed);
+    }
+
+    if shell.last.status != 0 && checked {
+        if let Some(status) = run_trap("ERR", shell) {
+            return Some(status);
+        }
+        if shell.errexit && !shell.in_trap {
+            return Some(shell.last.status);
+        }
+    }
+    None
+}
+
+/// Handles a declaration, `type name = value` or `type name=value`, which
+/// sets the variable and gives it a type that later assignments to it are
+/// checked against. The type is one of `int`, `float`, `char` or `bool`, or
+/// `long` or `double`, which are the same as `int` and `float`.
+///
+/// With `const` in front, as in `const int name = value`, or in place of
+/// the type, the variable is a constant and can't be assigned again.
+fn declare(constant: bool, ty: Option<Type>, words: &[&str], spans: &[Range<usize>], input: &str, shell: &mut Shell) -> i32 {
+    let keywords = usize::from(constant) + usize::from(ty.is_some());
+    let (rest, spans) = (&words[keywords..], &spans[keywords..]);
+    let declaration = match rest {
+        [name, "=", value] => Some((*name, *value, spans[2].clone())),
+        [word] => lexer::assignment(word).map(|(name, value)| (name, value, spans[0].end - value.len()..spans[0].end)),
+        _ => None,
+    };
+    let usage = words[..keywords].join(" ");
+    let Some((name, value, span)) = declaration.filter(|(_, value, _)| !value.is_empty()) else {
+        errln!(shell.streams, "pearsh: {}: usage: {} name = value", usage, usage);
+        return 2;
+    };
+    if lexer::variable_name(name) != Some(name) {
+        errln!(shell.streams, "pearsh: {}: `{}`: not a valid variable name", usage, name);
+        return 2;
+    }
+
+    let unquoted = lexer::unquote(value);
+    define(name, ty, constant, &unquoted, span, input, shell)
+}
+
+/// Handles `let name = value`, which declares a variable with the type of
+/// `value`, read as a literal by [`Value::parse_literal`]. The literal runs
+/// to the end of the command, so it can have spaces in it.
+fn declare_literal(words: &[&str], spans: &[Range<usize>], input: &str, shell: &mut Shell) -> i32 {
+    let end = spans[spans.len() - 1].end;
+    let declaration = match &words[1..] {
+        [name, "=", _, ..] => Some((*name, spans[3].start..end)),
+        [word, ..] => lexer::assignment(word)
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(name, value)| (name, spans[1].end - value.len()..end)),
+        _ => None,
+    };
+    let Some((name, span)) = declaration else {
+        errln!(shell.streams, "pearsh: let: usage: let name = value");
+        return 2;
+    };
+    if lexer::variable_name(name) != Some(name) {
+        errln!(shell.streams, "pearsh: let: `{}`: not a valid variable name", name);
+        return 2;
+    }
+
+    let value = match Value::parse_literal(&input[span.clone()]) {
+        Ok(value) => value,
+        Err(err) => {
+            let at = span.start + err.span.start..span.start + err.span.end;
+            let err = PearshError::InvalidLiteral(format!("{}: {}", name, err.message), at);
+            shell.report(&err, input);
+            return err.status();
+        }
+    };
+    define(name, Some(value.type_of()), false, &value.to_string(), span, input, shell)
+}
+
+/// Sets `name` to `value`, giving it type `ty`, if any, and making it a
+/// constant if `constant`. Reports why if it can't be, leaving the variable
+/// as it was.
+fn define(name: &str, ty: Option<Type>, constant: bool, value: &str, span: Range<usize>, input: &str, shell: &mut Shell) -> i32 {
+    let previous = ty.map(|ty| shell.types.insert(name.to_string(), ty));
+    match shell.assign(name, value, span) {
+        Ok(()) => {
+            if constant {
+                shell.constants.insert(name.to_string());
+            }
+            0
+        }
+        Err(err) => {
+            match previous {
+                Some(Some(previous)) => shell.types.insert(name.to_string(), previous),
+                Some(None) => shell.types.remove(name),
+                None => None,
+            };
+            shell.report(&err, input);
+            err.status()
+        }
+    }
+}
+
+/// Reads the arguments of a registered command, at `spans` of `input`
+/// after the command's name, as values of the types in `params`. Ints out
+/// of range are an error if `checked`.
+fn native_args(
+    name: &str,
+    params: &[Type],
+    input: &str,
+    spans: &[Range<usize>],
+    checked: bool,
+) -> Result<Vec<Value>, PearshError> {
+    let args = &spans[1..];
+    if args.len() != params.len() {
+        let plural = if params.len() == 1 { "" } else { "s" };
+        let message = format!("{}: expected {} argument{}, found {}", name, params.len(), plural, args.len());
+        let span = args.get(params.len()).map_or(spans[0].start..spans[spans.len() - 1].end, |extra| {
+            extra.start..spans[spans.len() - 1].end
+        });
+        return Err(PearshError::InvalidArgument(message, span));
+    }
+
+    params
+        .iter()
+        .zip(args)
+        .map(|(param, span)| {
+            let word = lexer::unquote(&input[span.clone()]);
+            value::read_value(name, *param, &word, checked).map_err(|message| PearshError::InvalidArgument(message, span.clone()))
+        })
+        .collect()
+}
+
+/// Why the command starting with `word` can't run: it isn't a builtin, a
+/// registered command or a name that can be looked up as written. Running
+/// nothing and succeeding would send a script down the wrong path, as
+/// `[ -e file ] && ...` would.
+fn unrunnable(word: &str, span: Range<usize>, shell: &Shell) -> Diagnostic {
+    let note = if word == "[" {
+        "tests with `[ ]` aren't supported yet".to_string()
+    } else if lexer::assignment(word).is_some_and(|(_, value)| value.starts_with('(')) {
+        "pearsh has no arrays yet".to_string()
+    } else if lexer::assignment(word).is_some() {
+        "assignments before a command aren't supported yet; assign on a line of its own".to_string()
+    } else if let Some(name) = lexer::expansion(word) {
+        match shell.var_type(name) {
+            Some(ty) if ty != Type::Bool => {
+                format!("`{}` is {}; only bool variables run as commands", name, ty.with_article())
+            }
+            _ => format!("only bool variables run as commands, and `{}` isn't one", name),
+        }
+    } else if word.contains(['$', '`']) {
+        "command names aren't expanded yet".to_string()
+    } else if word.contains(['\'', '"', '\\']) {
+        "command names aren't unquoted yet; write the name without quotes".to_string()
+    } else {
+        "only builtins and commands named as written can run".to_string()
+    };
+    Diagnostic::error(diagnostics::UNSUPPORTED_CONSTRUCT, format!("{}: can't be run as a command yet", word), span)
+        .note(note)
+}
+
+/// Whether `word`, the first word of a command, names a command that can be
+/// looked up as written. Assignments, reserved words and words that would
+/// need expanding first don't.
+fn is_command_name(word: &str) -> bool {
+    let plain = word.chars().all(|c| c.is_alphanumeric() || "-_./+:,@%".contains(c));
+    plain && !word.contains('=') && !RESERVED.contains(&word)
+}
+
+/// Finds the file that running `name` would execute: `name` itself if it
+/// contains a `/`, or else the first executable called `name` on `$PATH`.
+/// If there's no such executable, but there is a file that can't be
+/// executed, that's an error of its own. Errors point at `span`, where the
+/// name is in the command.
+fn find_command(name: &str, span: Range<usize>) -> Result<PathBuf, PearshError> {
+    let candidates: Vec<PathBuf> = if name.contains('/') {
+        vec![PathBuf::from(name)]
+    } else {
+        env::split_paths(&env::var_os("PATH").unwrap_or_default())
+            .flat_map(|dir| file_names(name).into_iter().map(move |file| dir.join(file)))
+            .collect()
+    };
+
+    if let Some(path) = candidates.iter().find(|path| complete::is_executable(path)) {
+        return Ok(path.clone());
+    }
+    match candidates.into_iter().find(|path| path.exists()) {
+        Some(path) => Err(PearshError::NotExecutable(path, span)),
+        None => Err(PearshError::NotFound(name.to_string(), span)),
+    }
+}
+
+/// The names a command called `name` might have on disk. On Windows, where
+/// `git` runs `git.exe`, these are `name` with each extension in
+/// `$PATHEXT`, unless it has an extension already. Elsewhere, it's just
+/// `name`.
+fn file_names(name: &str) -> Vec<String> {
+    if !cfg!(windows) || Path::new(name).extension().is_some() {
+        return vec![name.to_string()];
+    }
+    let extensions = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    extensions.split(';').filter(|ext| !ext.is_empty()).map(|ext| format!("{}{}", name, ext)).collect()
+}
+
+/// Runs the commands in a script file, returning the status to exit with:
+/// the last command's, or 127 or 126 if the script couldn't be found or
+/// read. A leading `#!` line is skipped.
+pub fn run_script(path: &str, shell: &mut Shell) -> i32 {
+    match run_file(path, shell) {
+        Ok(exit) => exit.unwrap_or(shell.last.status),
+        Err(err) => {
+            errln!(shell.streams, "pearsh: {}: {}", path, err);
+            if err.kind() == io::ErrorKind::NotFound { 127 } else { 126 }
+        }
+    }
+}
+
+/// Runs the commands in the file at `path`, each as soon as it's been read,
+/// so that a huge generated script starts running straight away and is
+/// never held in memory whole. A leading `#!` line is skipped.
+///
+/// Bash scripts are read whole first, so that constructs pearsh doesn't
+/// support are reported before any of the script runs; the status is 2
+/// if there are any. Returns the status to exit with if the file asked the
+/// shell to exit, or an error if it couldn't be opened.
+fn run_file(path: &str, shell: &mut Shell) -> io::Result<Option<i32>> {
+    let file = File::open(path)?;
+    if file.metadata()?.is_dir() {
+        return Err(io::ErrorKind::IsADirectory.into());
+    }
+    let mut lines = BufReader::new(file).lines();
+    let first = lines.next().transpose()?.unwrap_or_default();
+    let shebang = first.starts_with("#!");
+
+    if shell.dialect.unwrap_or_else(|| Dialect::detect(&first)) == Dialect::Bash {
+        let mut source = first;
+        for line in lines {
+            source.push('\n');
+            source.push_str(&line?);
+        }
+        if !runs_as(&source, path, shell) {
+            shell.last.status = 2;
+            return Ok(None);
+        }
+        let start = if shebang { source.find('\n').unwrap_or(source.len()) } else { 0 };
+        return Ok(run_string(&source[start..], path, shell));
+    }
+
+    // The `#!` line is left blank rather than left out, so that line
+    // numbers still match the file's.
+    let first = if shebang { String::new() } else { first };
+    Ok(run_lines(iter::once(Ok(first)).chain(lines), path, shell))
+}
+
+/// Whether `source`, from the file `name`, can run in its dialect. Bash
+/// constructs that pearsh doesn't support are reported, and stop the whole
+/// file from running rather than running as something else.
+pub fn runs_as(source: &str, name: &str, shell: &mut Shell) -> bool {
+    if shell.dialect.unwrap_or_else(|| Dialect::detect(source)) != Dialect::Bash {
+        return true;
+    }
+    let unsupported = dialect::unsupported_bash(source);
+    let runs = unsupported.is_empty();
+    shell.report_unsupported(unsupported, source, name);
+    runs
+}
+
+/// Handles `source file [args...]` and `. file [args...]`, which run the
+/// commands in a file in this shell, as [`run_file`] does, so that whatever
+/// they change, like the working directory or options, stays changed.
+///
+/// Given arguments, the file sees them as `$1` onwards, and `$#`, until it's
+/// done, when the positional parameters are put back as they were. Without
+/// any, it sees this shell's. The status is that of the file's last
+/// command. Returns the status to exit with if the file asked the shell to
+/// exit.
+fn source_builtin(words: &[&str], shell: &mut Shell) -> Option<i32> {
+    let [name, path, args @ ..] = words else {
+        errln!(shell.streams, "pearsh: {}: usage: {} file [args...]", words[0], words[0]);
+        shell.last.status = 2;
+        return None;
+    };
+    let path = lexer::unquote(path);
+    if shell.frames.len() >= MAX_SOURCE_DEPTH {
+        errln!(shell.streams, "pearsh: {}: {}: files are sourced too deeply", name, path);
+        shell.last.status = 1;
+        return None;
+    }
+
+    let saved = (!args.is_empty()).then(|| {
+        let saved: Vec<(String, String)> =
+            shell.vars.iter().filter(|(var, _)| is_positional(var)).map(|(var, value)| (var.clone(), value.clone())).collect();
+        let zero = shell.get_var("0").unwrap_or_default();
+        let args: Vec<String> = args.iter().map(|arg| lexer::unquote(arg)).collect();
+        shell.set_args(&zero, &args);
+        saved
+    });
+    let ran = run_file(&path, shell);
+    if let Some(saved) = saved {
+        shell.vars.retain(|var, _| !is_positional(var));
+        shell.vars.extend(saved);
+    }
+
+    match ran {
+        Ok(exit) => exit,
+        Err(err) => {
+            errln!(shell.streams, "pearsh: {}: {}: {}", name, path, err);
+            shell.last.status = 1;
+            None
+        }
+    }
+}
+
+/// Whether `var` is a positional parameter, like `$1`, or `$#`, which
+/// counts them.
+fn is_positional(var: &str) -> bool {
+    var == "#" || var.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Handles `import file [as name]`, which runs a file as `source` does,
+/// but with variables of its own: it starts with none set, and what it sets
+/// ends up as `name.var` in this shell, so that it can't clobber variables
+/// here. `name` is the file's name without its extension, unless given.
+/// Importing under the same name again replaces what was there. Returns the
+/// status to exit with if the file asked the shell to exit.
+///
+/// A relative path is found from the directory of the file doing the
+/// importing, so that a script can import files next to it from wherever
+/// it's run. Outside a file, it's found from the working directory.
+fn import_builtin(words: &[&str], shell: &mut Shell) -> Option<i32> {
+    let args: Vec<String> = words[1..].iter().map(|arg| lexer::unquote(arg)).collect();
+    let (path, name) = match args.as_slice() {
+        [path] => (path, Path::new(path).file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned())),
+        [path, as_, name] if as_ == "as" => (path, name.clone()),
+        _ => {
+            errln!(shell.streams, "pearsh: import: usage: import file [as name]");
+            shell.last.status = 2;
+            return None;
+        }
+    };
+    if lexer::variable_name(&name) != Some(name.as_str()) {
+        errln!(shell.streams, "pearsh: import: `{}`: not a valid module name", name);
+        shell.last.status = 2;
+        return None;
+    }
+    if shell.frames.len() >= MAX_SOURCE_DEPTH {
+        errln!(shell.streams, "pearsh: import: {}: files are sourced too deeply", path);
+        shell.last.status = 1;
+        return None;
+    }
+
+    let vars = mem::take(&mut shell.vars);
+    let types = mem::take(&mut shell.types);
+    let constants = mem::take(&mut shell.constants);
+    let importer = shell.frames.iter().rev().map(|frame| Path::new(&frame.name)).find(|file| file.is_file());
+    let path = match importer.and_then(Path::parent) {
+        Some(dir) => dir.join(path).to_string_lossy().into_owned(),
+        None => path.clone(),
+    };
+    let ran = run_file(&path, shell);
+    let module = (
+        mem::replace(&mut shell.vars, vars),
+        mem::replace(&mut shell.types, types),
+        mem::replace(&mut shell.constants, constants),
+    );
+
+    let exit = match ran {
+        Ok(exit) => exit,
+        Err(err) => {
+            errln!(shell.streams, "pearsh: import: {}: {}", path, err);
+            shell.last.status = 1;
+            return None;
+        }
+    };
+    let prefix = format!("{}.", name);
+    shell.vars.retain(|var, _| !var.starts_with(&prefix));
+    shell.types.retain(|var, _| !var.starts_with(&prefix));
+    shell.constants.retain(|var| !var.starts_with(&prefix));
+    shell.vars.extend(module.0.into_iter().map(|(var, value)| (format!("{}{}", prefix, var), value)));
+    shell.types.extend(module.1.into_iter().map(|(var, ty)| (format!("{}{}", prefix, var), ty)));
+    shell.constants.extend(module.2.into_iter().map(|var| format!("{}{}", prefix, var)));
+    exit
+}
+
+/// Runs a startup or logout file if it exists. Returns the status to exit
+/// with if the file asked the shell to exit.
+pub fn source_file(path: &Path, shell: &mut Shell) -> Option<i32> {
+    match fs::read_to_string(path) {
+        Ok(source) => run_string(&source, &path.display().to_string(), shell),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        Err(err) => {
+            errln!(shell.streams, "pearsh: {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// A file in the user's home directory, if `$HOME` is set.
+pub fn home_file(name: &str) -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(name))
+}
+
+/// Sources every file in the plugin directories, in order of name. These
+/// are the directories in `$PEARSH_PLUGIN_PATH`, separated by colons, or
+/// `~/.config/pearsh/plugins` if it isn't set. Returns the status to exit
+/// with if a plugin asked the shell to exit.
+pub fn load_plugins(shell: &mut Shell) -> Option<i32> {
+    let dirs: Vec<PathBuf> = match env::var_os("PEARSH_PLUGIN_PATH") {
+        Some(path) => env::split_paths(&path).filter(|dir| !dir.as_os_str().is_empty()).collect(),
+        None => home_file(".config/pearsh/plugins").into_iter().collect(),
+    };
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut plugins: Vec<PathBuf> =
+            entries.flatten().map(|entry| entry.path()).filter(|path| path.is_file()).collect();
+        plugins.sort();
+        for plugin in plugins {
+            if let Some(status) = source_file(&plugin, shell) {
+                return Some(status);
+            }
+        }
+    }
+    None
+}
+
+/// Runs `$PROMPT_COMMAND`, if it's set. Returns the status to exit with if
+/// it asked the shell to exit.
+fn prompt_command(shell: &mut Shell) -> Option<i32> {
+    let command = shell.get_var("PROMPT_COMMAND")?;
+    run_string(&command, "PROMPT_COMMAND", shell)
+}
+
+/// Runs the commands in `source`, as [`run_lines`] does. Since all of it is
+/// there to look at first, none of it runs if it uses constructs pearsh
+/// can't run yet, and the status is 2.
+pub fn run_string(source: &str, name: &str, shell: &mut Shell) -> Option<i32> {
+    let stripped = lexer::strip_comments(source);
+    let unsupported = match lexer::lex(&stripped) {
+        Ok(tokens) => dialect::unimplemented(&stripped, &tokens),
+        // Errors lexing it are reported when the command with them runs.
+        Err(_) => Vec::new(),
+    };
+    if !unsupported.is_empty() {
+        shell.report_unsupported(unsupported, source, name);
+        shell.last.status = 2;
+        return None;
+    }
+    run_lines(source.lines().map(|line| Ok(line.to_string())), name, shell)
+}
+
+/// Runs commands non-interactively. Returns the status to exit with if a
+/// command asked the shell to exit or the input couldn't be read.
+///
+/// Lines are grouped into commands the same way the REPL groups them, so
+/// a quote or pipeline can continue onto the next line. Each command runs
+/// as soon as it is complete, so input can be streamed. A command using a
+/// construct pearsh can't run yet stops the input there, with status 2.
+/// Errors are reported as coming from `name`.
+pub fn run_lines(lines: impl Iterator<Item = io::Result<String>>, name: &str, shell: &mut Shell) -> Option<i32> {
+    shell.frames.push(Location { name: name.to_string(), line: 1 });
+    let exit = run_commands(lines, shell);
+    shell.frames.pop();
+    exit
+}
+
+fn run_commands(lines: impl Iterator<Item = io::Result<String>>, shell: &mut Shell) -> Option<i32> {
+    let mut input = String::new();
+    let mut continuation = lexer::Continuation::default();
+    for (number, line) in (1..).zip(lines) {
+        if input.is_empty() {
+            if let Some(frame) = shell.frames.last_mut() {
+                frame.line = number;
+            }
+        }
+
+        match line {
+            Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+                continuation.push(&line);
+                continuation.push("\n");
+            }
+            Err(err) => {
+                errln!(shell.streams, "pearsh: error reading input: {}", err);
+                return Some(1);
+            }
+        }
+
+        if continuation.is_incomplete() {
+            continue;
+        }
+        match try_execute(&input, shell) {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) => {}
+            Err(err) => {
+                shell.report(&err, &input);
+                shell.last.status = err.status();
+                // What comes after a construct that couldn't run may be
+                // part of it, like the body of an `if`, so it can't run
+                // either.
+                if matches!(err, PearshError::Unsupported(_)) {
+                    return None;
+                }
+            }
+        }
+        input.clear();
+        continuation = lexer::Continuation::default();
+    }
+
+    if !input.is_empty() {
+        return execute(&input, shell);
+    }
+    None
+}
+
+/// Runs the interactive read-execute loop, returning the status to exit with.
+/// Plugins are loaded first, as by [`load_plugins`].
+pub fn repl(shell: &mut Shell) -> i32 {
+    // Plugins can set `$HISTFILE`, so the history is loaded after them.
+    if let Some(status) = load_plugins(shell) {
+        return status;
+    }
+    load_history(shell);
+
+    // The input is read into the same buffer each time, which keeps the
+    // capacity of the longest command so far.
+    let mut input = String::new();
+    let mut eofs = 0;
+    let status = loop {
+        if let Some(status) = prompt_command(shell) {
+            break status;
+        }
+        for hook in &mut shell.hooks {
+            hook.on_prompt();
+        }
+        if shell.title {
+            set_title(&prompt::render("\\u@\\h: \\w", None, &shell.last));
+        }
+        share_history(shell);
+
+        match read_command(shell, &mut input) {
+            Ok(true) => {}
+            Ok(false) if eofs < ignoreeof(shell) => {
+                eofs += 1;
+                eprintln!("Use \"exit\" to leave the shell.");
+                continue;
+            }
+            Ok(false) => break shell.last.status,
+            Err(err) => {
+                eprintln!("pearsh: error reading input: {}", err);
+                break 1;
+            }
+        }
+        eofs = 0;
+        share_history(shell);
+
+        if shell.title {
+            set_title(input.lines().next().unwrap_or_default());
+        }
+        let start = Instant::now();
+        let exit = execute(&input, shell);
+        shell.last.elapsed = start.elapsed();
+        if let Some(status) = exit {
+            break status;
+        }
+    };
+
+    if let Some(history) = &mut shell.history {
+        if let Err(err) = history.save() {
+            history_error(history, err);
+        }
+    }
+    status
+}
+
+/// With `sharehistory` on, syncs history with other shells.
+fn share_history(shell: &mut Shell) {
+    if let (true, Some(history)) = (shell.sharehistory, &mut shell.history) {
+        if let Err(err) = history.sync() {
+            history_error(history, err);
+        }
+    }
+}
+
+fn history_error(history: &History, err: io::Error) {
+    let path = history.path().map(|p| p.display().to_string()).unwrap_or_default();
+    eprintln!("pearsh: {}: {}", path, err);
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use crate::diagnostics::{self, Diagnostic};
+use crate::lexer::{self, TokenKind};
+use crate::lint;
+use crate::value::{self, Type, Value};
+
+/// Where a variable was declared, with what type, if any, and whether
+/// it's a constant.
+struct Declaration {
+    ty: Option<Type>,
+    constant: bool,
+    line: usize,
+}
+
+/// Checks the values given to typed variables and constants in `source`
+/// without running it, returning an error for each assignment that would
+/// fail, in the order they appear.
+///
+/// Values are checked as running the script would check them, once their
+/// quotes are removed. Nothing expands variables yet, so a value like `$x`
+/// is the text `$x`, which only fits in a string, whatever `x` holds.
+/// Anything fits in an undeclared variable, and nothing fits in a constant
+/// once it's been declared.
+///
+/// Declarations are taken in the order they're written. Scripts that use
+/// `if` or `while` don't run at all, so which branch a declaration is in
+/// doesn't matter. What can't be seen without running the script is
+/// whether `checkedarith` is on, so ints out of range are let through.
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    let source = lexer::strip_comments(source);
+    let tokens = lexer::tokenize(source.clone());
+
+    let mut declarations: BTreeMap<&str, Declaration> = BTreeMap::new();
+    let mut errors = Vec::new();
+    for command in lint::commands(&tokens) {
+        let words = lexer::words(command.tokens);
+        let line = source[..command.span().start].matches('\n').count() + 1;
+
+        let keyword = |i: usize| {
+            let word = words.get(i)?;
+            command.tokens.iter().find(|token| token.span == *word).map(|token| token.kind)
+        };
+        let (constant, declared) = match (keyword(0), keyword(1)) {
+            (Some(TokenKind::Const), second) => (true, second.and_then(Type::from_token)),
+            (first, _) => (false, first.and_then(Type::from_token)),
+        };
+
+        if constant || declared.is_some() {
+            let keywords = usize::from(constant) + usize::from(declared.is_some());
+            let Some((name, value)) = declaration(&source, &words[keywords - 1..]) else {
+                continue;
+            };
+            let ty = declared.or_else(|| declarations.get(name)?.ty);
+            // As when it runs, a declaration that fails leaves the
+            // variable as it was.
+            match assigned(&source, name, ty, value, &declarations) {
+                Some(error) => errors.push(error),
+                None => {
+                    declarations.insert(name, Declaration { ty, constant, line });
+                }
+            }
+        } else if keyword(0) == Some(TokenKind::Let) {
+            let Some((name, value)) = literal_declaration(&source, &words) else {
+                continue;
+            };
+            match Value::parse_literal(&source[value.clone()]) {
+                Ok(literal) => match assigned(&source, name, None, value, &declarations) {
+                    Some(error) => errors.push(error),
+                    None => {
+                        declarations.insert(name, Declaration { ty: Some(literal.type_of()), constant: false, line });
+                    }
+                },
+                Err(err) => errors.push(Diagnostic::error(
+                    diagnostics::INVALID_LITERAL,
+                    format!("{}: {}", name, err.message),
+                    value.start + err.span.start..value.start + err.span.end,
+                )),
+            }
+        } else if words.iter().all(|word| lexer::assignment(&source[word.clone()]).is_some()) {
+            for (name, value) in words.iter().filter_map(|word| assignment(&source, word.clone())) {
+                let ty = declarations.get(name).and_then(|declaration| declaration.ty);
+                errors.extend(assigned(&source, name, ty, value, &declarations));
+            }
+        }
+    }
+    errors
+}
+
+/// An error if the value at `span` can't be given to `name`, either
+/// because it's a constant or because the value doesn't fit `ty`, the
+/// type it's declared with.
+fn assigned(
+    source: &str,
+    name: &str,
+    ty: Option<Type>,
+    span: Range<usize>,
+    declarations: &BTreeMap<&str, Declaration>,
+) -> Option<Diagnostic> {
+    let declaration = declarations.get(name);
+    if let Some(declaration) = declaration.filter(|declaration| declaration.constant) {
+        let message = format!("{}: can't assign to a constant", name);
+        return Some(
+            Diagnostic::error(diagnostics::CONSTANT_ASSIGNMENT, message, span)
+                .note(format!("`{}` was declared constant on line {}", name, declaration.line)),
+        );
+    }
+
+    let error = mismatch(source, name, ty?, span)?;
+    match declaration.filter(|declaration| declaration.ty == ty) {
+        Some(declaration) => Some(error.note(format!(
+            "`{}` was declared {} on line {}",
+            name,
+            ty?.with_article(),
+            declaration.line
+        ))),
+        None => Some(error),
+    }
+}
+
+/// The name a declaration declares and the span of its value, for either
+/// `keyword name = value` or `keyword name=value`.
+fn declaration<'a>(source: &'a str, words: &[Range<usize>]) -> Option<(&'a str, Range<usize>)> {
+    match words {
+        [_, name, equals, value] if &source[equals.clone()] == "=" => {
+            let name = &source[name.clone()];
+            (lexer::variable_name(name) == Some(name)).then(|| (name, value.clone()))
+        }
+        [_, word] => assignment(source, word.clone()),
+        _ => None,
+    }
+}
+
+/// The name `let name = value` declares and the span of its value, which
+/// runs to the end of the command.
+fn literal_declaration<'a>(source: &'a str, words: &[Range<usize>]) -> Option<(&'a str, Range<usize>)> {
+    let end = words.last()?.end;
+    match words {
+        [_, name, equals, value, ..] if &source[equals.clone()] == "=" => {
+            let name = &source[name.clone()];
+            (lexer::variable_name(name) == Some(name)).then_some((name, value.start..end))
+        }
+        [_, word, ..] => {
+            let (name, value) = assignment(source, word.clone()).filter(|(_, value)| !value.is_empty())?;
+            Some((name, value.start..end))
+        }
+        _ => None,
+    }
+}
+
+/// The name a `name=value` word assigns to and the span of its value.
+fn assignment(source: &str, word: Range<usize>) -> Option<(&str, Range<usize>)> {
+    let (name, value) = lexer::assignment(&source[word.clone()])?;
+    Some((name, word.end - value.len()..word.end))
+}
+
+/// An error if the value at `span` can't be given to `name`, which has
+/// type `ty`.
+fn mismatch(source: &str, name: &str, ty: Type, span: Range<usize>) -> Option<Diagnostic> {
+    let value = lexer::unquote(&source[span.clone()]);
+    // Whether an int out of range is an error depends on `checkedarith`,
+    // which is only known once the script runs.
+    let message = value::read_value(name, ty, &value, false).err()?;
+    Some(Diagnostic::error(diagnostics::TYPE_MISMATCH, message, span))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The messages of the errors `check` finds in `source`.
+    fn errors(source: &str) -> Vec<String> {
+        check(source).into_iter().map(|diagnostic| diagnostic.message).collect()
+    }
+
+    #[test]
+    fn literals_are_checked_against_the_declared_type() {
+        assert_eq!(errors("int x = 5\nfloat f = 1.5\nchar c = 'a'\nbool b = true"), Vec::<String>::new());
+        assert_eq!(errors("int x = hi"), ["x: expected an int, found `hi`"]);
+        assert_eq!(errors("int x = 5\nx=1.5"), ["x: expected an int, found `1.5`"]);
+    }
+
+    #[test]
+    fn expansions_are_checked_as_the_text_they_are() {
+        assert_eq!(errors("int x = 5\nint y = $x"), ["y: expected an int, found `$x`"]);
+        assert_eq!(errors("int y = \"${x}\""), ["y: expected an int, found `${x}`"]);
+        assert_eq!(errors("int x = 5\nx=$x"), ["x: expected an int, found `$x`"]);
+        assert_eq!(errors("int x = 5\ny=$x"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn ints_out_of_range_are_left_to_checkedarith() {
+        assert_eq!(errors("int x = 99999999999999999999"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn constants_cant_be_assigned_again() {
+        assert_eq!(errors("const int x = 5\nx=6"), ["x: can't assign to a constant"]);
+    }
+
+    #[test]
+    fn comments_are_left_out() {
+        assert_eq!(errors("int x = 5 # it's five"), Vec::<String>::new());
+    }
+}
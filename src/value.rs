@@ -0,0 +1,698 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::lexer::TokenKind;
+
+/// The type of a [`Value`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Type {
+    /// A signed 64-bit int, from -9223372036854775808 to
+    /// 9223372036854775807. Ints written outside that range wrap around,
+    /// as they do in bash, unless `set -o checkedarith` is on, which makes
+    /// them an error instead. Unsigned 64-bit values past the top of the
+    /// range come out negative, so `18446744073709551615` is -1.
+    Int,
+    Float,
+    Char,
+    Str,
+    /// `true` or `false`. Bools and exit statuses convert into each other
+    /// the way the `true` and `false` commands do: a bool run as a command,
+    /// as in `$flag` or `if $flag`, succeeds if it's true and fails with
+    /// status 1 if it's false, and only a status of 0 is true.
+    Bool,
+    /// A list of values, written as in `[80, 443]`.
+    List,
+    /// Values by string keys, written as in `{"host": "x", "port": 80}`.
+    Map,
+}
+
+impl Type {
+    /// The type's name, as used in error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Char => "char",
+            Type::Str => "str",
+            Type::Bool => "bool",
+            Type::List => "list",
+            Type::Map => "map",
+        }
+    }
+
+    /// The type a type keyword, as in `int x = 5`, declares. Values are 64
+    /// bits wide either way, so `long` and `double` are the same as `int`
+    /// and `float`.
+    pub fn from_token(kind: TokenKind) -> Option<Type> {
+        match kind {
+            TokenKind::TypeInt | TokenKind::TypeLong => Some(Type::Int),
+            TokenKind::TypeFloat | TokenKind::TypeDouble => Some(Type::Float),
+            TokenKind::TypeChar => Some(Type::Char),
+            TokenKind::TypeBool => Some(Type::Bool),
+            _ => None,
+        }
+    }
+
+    /// Reads a value of this type from a word, once it's been unquoted.
+    /// Ints are decimal, with an optional sign, floats are anything Rust
+    /// reads as an `f64`, chars are a single character and bools are `true`
+    /// or `false`. Lists and maps are read as literals, as by
+    /// [`Value::parse_literal`]. Any word is a string.
+    pub fn parse(self, word: &str) -> Option<Value> {
+        match self {
+            Type::Int => word.parse().ok().map(Value::Int),
+            Type::Float => float(word).map(Value::Float),
+            Type::Char => word.parse().ok().map(Value::Char),
+            Type::Str => Some(Value::Str(word.to_string())),
+            Type::Bool => word.parse().ok().map(Value::Bool),
+            Type::List | Type::Map => Value::parse_literal(word).ok().filter(|value| value.type_of() == self),
+        }
+    }
+
+    /// The type's name with "a" or "an" in front, as in "expected an int".
+    pub fn with_article(self) -> String {
+        let article = if self == Type::Int { "an" } else { "a" };
+        format!("{} {}", article, self)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Reads a decimal int, with an optional sign, wrapping it around into
+/// the range of an `i64` if it's outside it, as bash does. `None` if `word`
+/// isn't an int at all.
+pub fn wrapping_int(word: &str) -> Option<i64> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word.strip_prefix('+').unwrap_or(word)),
+    };
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let int = digits.bytes().fold(0i64, |int, digit| int.wrapping_mul(10).wrapping_add(i64::from(digit - b'0')));
+    Some(if negative { int.wrapping_neg() } else { int })
+}
+
+/// Reads a float written as a number, as in `1.5`, `-2` or `6.02e23`.
+/// Unlike `str::parse`, words like `inf` and `nan` aren't floats, and
+/// neither is a number too big for one.
+fn float(word: &str) -> Option<f64> {
+    let numeric = word.bytes().all(|byte| byte.is_ascii_digit() || b"+-.eE".contains(&byte));
+    if !numeric || !word.bytes().any(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    word.parse().ok().filter(|float: &f64| float.is_finite())
+}
+
+/// Whether `word` is a number as JSON writes them: an optional `-`, an int
+/// with no leading zeros, then an optional fraction and exponent.
+fn is_json_number(word: &str) -> bool {
+    let digits = |text: &str| text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = word.strip_prefix('-').unwrap_or(word);
+    let int = digits(rest);
+    if int == 0 || (int > 1 && rest.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &rest[int..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        if digits(fraction) == 0 {
+            return false;
+        }
+        rest = &fraction[digits(fraction)..];
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        return digits(exponent) > 0 && digits(exponent) == exponent.len();
+    }
+    rest.is_empty()
+}
+
+/// Reads `word`, given to `name`, as a value of type `ty`, or says why it
+/// isn't one. An int out of range wraps around, unless `checked`.
+pub(crate) fn read_value(name: &str, ty: Type, word: &str, checked: bool) -> Result<Value, String> {
+    if let Some(value) = ty.parse(word) {
+        return Ok(value);
+    }
+    match wrapping_int(word).filter(|_| ty == Type::Int) {
+        Some(_) if checked => Err(format!("{}: `{}` is out of range for an int", name, word)),
+        Some(int) => Ok(Value::Int(int)),
+        None => Err(format!("{}: expected {}, found `{}`", name, ty.with_article(), word)),
+    }
+}
+
+/// A typed value, as passed to commands registered with
+/// [`Shell::register_command`](crate::Shell::register_command).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Char(char),
+    Str(String),
+    Bool(bool),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+/// Why a literal couldn't be read, and the span of it that's to blame.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LiteralError {
+    pub message: String,
+    pub span: Range<usize>,
+}
+
+impl Value {
+    pub fn type_of(&self) -> Type {
+        match self {
+            Value::Int(_) => Type::Int,
+            Value::Float(_) => Type::Float,
+            Value::Char(_) => Type::Char,
+            Value::Str(_) => Type::Str,
+            Value::Bool(_) => Type::Bool,
+            Value::List(_) => Type::List,
+            Value::Map(_) => Type::Map,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(int) => Some(*int),
+            _ => None,
+        }
+    }
+
+    /// The value as a float. Ints convert, since any int argument would
+    /// also be a valid float.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(float) => Some(*float),
+            Value::Int(int) => Some(*int as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_char(&self) -> Option<char> {
+        match self {
+            Value::Char(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(list) => Some(list),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Indexes into a list by position, counting from 0, or a map by key.
+    pub fn get(&self, index: &str) -> Option<&Value> {
+        match self {
+            Value::List(list) => list.get(index.parse::<usize>().ok()?),
+            Value::Map(map) => map.get(index),
+            _ => None,
+        }
+    }
+
+    /// Reads a value written as a literal, as `let` does.
+    ///
+    /// Lists are written as in `[1, 2, 3]` and maps as in `{"a": 1, "b":
+    /// [2]}`, nested as deep as need be. Strings are quoted, with JSON's
+    /// escapes inside double quotes and none inside single quotes, though
+    /// words without spaces or any of `[]{},:` in them can go unquoted.
+    /// Unquoted words that read as ints, floats or bools are those instead,
+    /// and `null` is an empty string, so that any JSON can be read too. A
+    /// literal that's a single word or string is that value on its own.
+    pub fn parse_literal(text: &str) -> Result<Value, LiteralError> {
+        Parser { text, pos: 0, json: false }.parse()
+    }
+
+    /// Reads a value written as JSON, as `from-json` does. This is stricter
+    /// than [`Value::parse_literal`]: strings and keys have to be in double
+    /// quotes, and the only unquoted words are numbers as JSON writes them,
+    /// `true`, `false` and `null`.
+    pub fn parse_json(text: &str) -> Result<Value, LiteralError> {
+        Parser { text, pos: 0, json: true }.parse()
+    }
+
+    /// The value as JSON. Strings and chars are quoted even on their own,
+    /// and floats that JSON can't represent, like infinity, are `null`.
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Str(string) => quote(string),
+            Value::Char(c) => quote(&c.to_string()),
+            Value::Float(float) if !float.is_finite() => "null".to_string(),
+            Value::List(list) => format!("[{}]", list.iter().map(Value::to_json).collect::<Vec<_>>().join(", ")),
+            Value::Map(map) => {
+                let entries: Vec<String> =
+                    map.iter().map(|(key, value)| format!("{}: {}", quote(key), value.to_json())).collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            value => value.to_string(),
+        }
+    }
+
+    /// The exit status a bool stands for: 0 for true and 1 for false.
+    /// Other values aren't statuses.
+    pub fn status(&self) -> Option<i32> {
+        self.as_bool().map(|bool| if bool { 0 } else { 1 })
+    }
+}
+
+/// Values display as they'd be written in a command, so that they read
+/// back as the same value. Strings and chars in a list or map are quoted,
+/// but not on their own.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let item = |value: &Value| match value {
+            Value::Str(string) => quote(string),
+            Value::Char(c) => quote(&c.to_string()),
+            value => value.to_string(),
+        };
+        match self {
+            Value::Int(int) => write!(f, "{}", int),
+            Value::Float(float) => write!(f, "{:?}", float),
+            Value::Char(c) => write!(f, "{}", c),
+            Value::Str(string) => f.write_str(string),
+            Value::Bool(bool) => write!(f, "{}", bool),
+            Value::List(list) => write!(f, "[{}]", list.iter().map(item).collect::<Vec<_>>().join(", ")),
+            Value::Map(map) => {
+                let entries: Vec<String> = map.iter().map(|(key, value)| format!("{}: {}", quote(key), item(value))).collect();
+                write!(f, "{{{}}}", entries.join(", "))
+            }
+        }
+    }
+}
+
+/// Quotes a string as JSON does.
+pub(crate) fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Reads literals for [`Value::parse_literal`], or JSON for
+/// [`Value::parse_json`] if `json`, from `pos` onwards.
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    json: bool,
+}
+
+impl Parser<'_> {
+    /// Reads the whole of the text as one value.
+    fn parse(&mut self) -> Result<Value, LiteralError> {
+        let value = self.value()?;
+        self.skip_space();
+        if self.pos < self.text.len() {
+            return Err(self.expected("the end of the value"));
+        }
+        Ok(value)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn skip_space(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /// Skips past `c` if it's next, after any whitespace.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    /// An error for finding something other than `what` at `pos`.
+    fn expected(&self, what: &str) -> LiteralError {
+        match self.peek() {
+            Some(c) => LiteralError {
+                message: format!("expected {}, found `{}`", what, c),
+                span: self.pos..self.pos + c.len_utf8(),
+            },
+            None => LiteralError { message: format!("expected {}", what), span: self.pos..self.pos },
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, LiteralError> {
+        self.skip_space();
+        match self.peek() {
+            Some('[') => self.list(),
+            Some('{') => self.map(),
+            Some('\'') if self.json => Err(self.expected("a value")),
+            Some('"' | '\'') => self.string().map(Value::Str),
+            Some(c) if !is_delimiter(c) => self.word_value(),
+            _ => Err(self.expected("a value")),
+        }
+    }
+
+    fn list(&mut self) -> Result<Value, LiteralError> {
+        self.pos += 1;
+        let mut list = Vec::new();
+        while !self.eat(']') {
+            list.push(self.value()?);
+            if !self.eat(',') && self.peek() != Some(']') {
+                return Err(self.expected("`,` or `]`"));
+            }
+        }
+        Ok(Value::List(list))
+    }
+
+    fn map(&mut self) -> Result<Value, LiteralError> {
+        self.pos += 1;
+        let mut map = BTreeMap::new();
+        while !self.eat('}') {
+            let key = match self.peek() {
+                Some('"') => self.string()?,
+                Some('\'') if !self.json => self.string()?,
+                Some(c) if !is_delimiter(c) && !self.json => self.word().to_string(),
+                _ => return Err(self.expected("a key")),
+            };
+            if !self.eat(':') {
+                return Err(self.expected("`:`"));
+            }
+            map.insert(key, self.value()?);
+            if !self.eat(',') && self.peek() != Some('}') {
+                return Err(self.expected("`,` or `}`"));
+            }
+        }
+        Ok(Value::Map(map))
+    }
+
+    /// A quoted string, with escapes read if it's in double quotes.
+    fn string(&mut self) -> Result<String, LiteralError> {
+        let start = self.pos;
+        let quote = self.peek().expect("strings start with a quote");
+        self.pos += 1;
+
+        let mut string = String::new();
+        let mut chars = self.text[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(string);
+                }
+                '\\' if quote == '"' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => {
+                            let escape = self.pos + i;
+                            let (c, len) = unicode_escape(&self.text[escape..]).map_err(|err| LiteralError {
+                                message: err.message,
+                                span: escape + err.span.start..escape + err.span.end,
+                            })?;
+                            // The `\` and `u` have been read already, and the
+                            // rest of a valid escape is ASCII.
+                            chars.nth(len - 3);
+                            c
+                        }
+                        Some(c) => c,
+                        None => break,
+                    };
+                    string.push(escaped);
+                }
+                c => string.push(c),
+            }
+        }
+        Err(LiteralError { message: "unterminated string".to_string(), span: start..self.text.len() })
+    }
+
+    /// The value an unquoted word stands for. In JSON, a word that isn't a
+    /// number, `true`, `false` or `null` is an error.
+    fn word_value(&mut self) -> Result<Value, LiteralError> {
+        let start = self.pos;
+        let json = self.json;
+        let word = self.word();
+        let error = |message: String| LiteralError { message, span: start..start + word.len() };
+        match word {
+            _ if !json => Ok(word_value(word)),
+            "true" | "false" | "null" => Ok(word_value(word)),
+            _ if !is_json_number(word) => Err(error(format!("expected a value, found `{}`", word))),
+            _ => match word_value(word) {
+                Value::Str(_) => Err(error(format!("`{}` is too big for a number", word))),
+                number => Ok(number),
+            },
+        }
+    }
+
+    /// An unquoted word.
+    fn word(&mut self) -> &str {
+        let start = self.pos;
+        let len = self.text[start..].find(|c: char| c.is_whitespace() || is_delimiter(c)).unwrap_or(self.text.len() - start);
+        self.pos += len;
+        &self.text[start..self.pos]
+    }
+}
+
+/// Reads the `\uXXXX` escape at the start of `text`, along with the one
+/// after it if the two are a UTF-16 surrogate pair, as JSON writes
+/// characters outside the Basic Multilingual Plane. Returns the character
+/// and how many bytes of `text` the escapes take up. A surrogate that isn't
+/// part of a pair is an error, since it isn't a character.
+fn unicode_escape(text: &str) -> Result<(char, usize), LiteralError> {
+    let hex = |at: usize| {
+        let digits = text.get(at..at + 6)?.strip_prefix("\\u")?;
+        digits.bytes().all(|byte| byte.is_ascii_hexdigit()).then(|| u32::from_str_radix(digits, 16).ok())?
+    };
+    let error = |message: &str, chars: usize| LiteralError {
+        message: message.to_string(),
+        span: 0..text.char_indices().nth(chars).map_or(text.len(), |(i, _)| i),
+    };
+
+    match hex(0) {
+        Some(high @ 0xd800..=0xdbff) => match hex(6) {
+            Some(low @ 0xdc00..=0xdfff) => {
+                let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                Ok((char::from_u32(code).expect("surrogate pairs make characters"), 12))
+            }
+            _ => Err(error("unpaired surrogate in `\\u` escape", 6)),
+        },
+        Some(0xdc00..=0xdfff) => Err(error("unpaired surrogate in `\\u` escape", 6)),
+        Some(code) => Ok((char::from_u32(code).expect("only surrogates aren't characters"), 6)),
+        None => Err(error("invalid `\\u` escape", 6)),
+    }
+}
+
+/// Whether `c` can't be part of an unquoted word in a literal.
+fn is_delimiter(c: char) -> bool {
+    "[]{},:\"'".contains(c)
+}
+
+/// The value an unquoted word in a literal stands for.
+fn word_value(word: &str) -> Value {
+    if let Ok(int) = word.parse() {
+        return Value::Int(int);
+    }
+    match float(word) {
+        Some(float) => Value::Float(float),
+        None if word == "null" => Value::Str(String::new()),
+        None => word.parse().map_or_else(|_| Value::Str(word.to_string()), Value::Bool),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapping_int_reads_ints_in_range() {
+        assert_eq!(wrapping_int("0"), Some(0));
+        assert_eq!(wrapping_int("+42"), Some(42));
+        assert_eq!(wrapping_int("-42"), Some(-42));
+        assert_eq!(wrapping_int("9223372036854775807"), Some(i64::MAX));
+        assert_eq!(wrapping_int("-9223372036854775808"), Some(i64::MIN));
+    }
+
+    #[test]
+    fn wrapping_int_wraps_ints_out_of_range() {
+        assert_eq!(wrapping_int("9223372036854775808"), Some(i64::MIN));
+        assert_eq!(wrapping_int("-9223372036854775809"), Some(i64::MAX));
+        assert_eq!(wrapping_int("18446744073709551615"), Some(-1));
+        assert_eq!(wrapping_int("18446744073709551616"), Some(0));
+    }
+
+    #[test]
+    fn wrapping_int_rejects_words_that_arent_ints() {
+        for word in ["", "-", "+", "1.5", "0x10", "1e3", "--1", " 1", "١"] {
+            assert_eq!(wrapping_int(word), None, "{:?}", word);
+        }
+    }
+
+    #[test]
+    fn read_value_wraps_ints_unless_checked() {
+        assert_eq!(read_value("n", Type::Int, "9223372036854775808", false), Ok(Value::Int(i64::MIN)));
+        assert_eq!(
+            read_value("n", Type::Int, "9223372036854775808", true),
+            Err("n: `9223372036854775808` is out of range for an int".to_string())
+        );
+    }
+
+    #[test]
+    fn read_value_reads_the_ends_of_the_range_either_way() {
+        for checked in [false, true] {
+            assert_eq!(read_value("n", Type::Int, "-9223372036854775808", checked), Ok(Value::Int(i64::MIN)));
+            assert_eq!(read_value("n", Type::Int, "9223372036854775807", checked), Ok(Value::Int(i64::MAX)));
+        }
+    }
+
+    #[test]
+    fn read_value_reads_u64_max_as_minus_one_unless_checked() {
+        assert_eq!(read_value("n", Type::Int, "18446744073709551615", false), Ok(Value::Int(-1)));
+        assert!(read_value("n", Type::Int, "18446744073709551615", true).is_err());
+    }
+
+    #[test]
+    fn read_value_only_wraps_ints() {
+        assert_eq!(
+            read_value("f", Type::Float, "nope", false),
+            Err("f: expected a float, found `nope`".to_string())
+        );
+        assert_eq!(
+            read_value("c", Type::Char, "99999999999999999999", false),
+            Err("c: expected a char, found `99999999999999999999`".to_string())
+        );
+        assert_eq!(read_value("s", Type::Str, "99999999999999999999", true), Ok(Value::Str("99999999999999999999".to_string())));
+    }
+
+    #[test]
+    fn literals_read_the_ends_of_the_int_range() {
+        assert_eq!(Value::parse_literal("-9223372036854775808"), Ok(Value::Int(i64::MIN)));
+        assert_eq!(
+            Value::parse_literal("[9223372036854775807, -9223372036854775808]"),
+            Ok(Value::List(vec![Value::Int(i64::MAX), Value::Int(i64::MIN)]))
+        );
+    }
+
+    #[test]
+    fn literals_read_u64_max_as_a_float() {
+        // Literals read JSON, where a number too big for an int is still a
+        // number, so it doesn't wrap the way a declared int does.
+        assert_eq!(Value::parse_literal("18446744073709551615"), Ok(Value::Float(18446744073709551615.0)));
+    }
+
+    #[test]
+    fn json_escapes_read_surrogate_pairs() {
+        assert_eq!(Value::parse_literal(r#""\ud83d\ude00""#), Ok(Value::Str("😀".to_string())));
+        assert_eq!(Value::parse_literal(r#""a\u00e9\ud834\udd1eb""#), Ok(Value::Str("aé𝄞b".to_string())));
+    }
+
+    #[test]
+    fn json_escapes_reject_lone_surrogates() {
+        for json in [r#""\ud83d""#, r#""\ud83dx""#, r#""\ude00\ud83d""#, r#""\ud83d\u0041""#] {
+            assert_eq!(
+                Value::parse_literal(json),
+                Err(LiteralError { message: "unpaired surrogate in `\\u` escape".to_string(), span: 1..7 }),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn json_escapes_need_four_hex_digits() {
+        for json in [r#""\u12""#, r#""\u+123""#, r#""\u12é4""#] {
+            let err = Value::parse_literal(json).expect_err(json);
+            assert_eq!(err.message, "invalid `\\u` escape", "{}", json);
+        }
+    }
+
+    #[test]
+    fn floats_are_only_written_as_numbers() {
+        assert_eq!(Type::Float.parse("1.5"), Some(Value::Float(1.5)));
+        assert_eq!(Type::Float.parse("-2"), Some(Value::Float(-2.0)));
+        assert_eq!(Type::Float.parse("6.02e23"), Some(Value::Float(6.02e23)));
+        for word in ["nan", "NaN", "inf", "-inf", "infinity", "1e999", "e", ".", "-"] {
+            assert_eq!(Type::Float.parse(word), None, "{:?}", word);
+        }
+        // Elsewhere in a literal, they're words like any other.
+        assert_eq!(
+            Value::parse_literal("[nan, inf]"),
+            Ok(Value::List(vec![Value::Str("nan".to_string()), Value::Str("inf".to_string())]))
+        );
+    }
+
+    #[test]
+    fn json_reads_json() {
+        assert_eq!(
+            Value::parse_json(r#"{"a": [1, -0.5, 2e3, true, null, "s"]}"#),
+            Ok(Value::Map(BTreeMap::from([(
+                "a".to_string(),
+                Value::List(vec![
+                    Value::Int(1),
+                    Value::Float(-0.5),
+                    Value::Float(2000.0),
+                    Value::Bool(true),
+                    Value::Str(String::new()),
+                    Value::Str("s".to_string()),
+                ])
+            )])))
+        );
+    }
+
+    #[test]
+    fn json_only_has_numbers_true_false_and_null_unquoted() {
+        for json in ["nan", "inf", "-inf", "NaN", "word", "01", "+1", ".5", "1.", "1e", "0x10", "[1, nan]"] {
+            let err = Value::parse_json(json).expect_err(json);
+            assert!(err.message.starts_with("expected a value, found"), "{}: {}", json, err.message);
+        }
+        let err = Value::parse_json("1e400").expect_err("1e400");
+        assert_eq!(err.message, "`1e400` is too big for a number");
+    }
+
+    #[test]
+    fn json_has_double_quoted_strings_and_keys() {
+        assert!(Value::parse_json("'a'").is_err());
+        assert!(Value::parse_json("{a: 1}").is_err());
+        assert!(Value::parse_json("{'a': 1}").is_err());
+        assert!(Value::parse_literal("{a: 'b'}").is_ok());
+    }
+}
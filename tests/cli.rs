@@ -0,0 +1,259 @@
+//! The `pearsh` binary's command line: its flags, how it sets `$0` and the
+//! positional parameters, and the statuses it exits with.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Runs `pearsh` with `args`, in `dir` if given, with no startup files.
+fn pearsh_in(dir: Option<&PathBuf>, args: &[&str]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_pearsh"));
+    command.args(args).env("HOME", env!("CARGO_TARGET_TMPDIR")).env_remove("PEARSH_PLUGIN_PATH");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    command.output().expect("pearsh runs")
+}
+
+fn pearsh(args: &[&str]) -> Output {
+    pearsh_in(None, args)
+}
+
+fn status(output: &Output) -> i32 {
+    output.status.code().expect("pearsh exits rather than being killed")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+/// A fresh directory for a test's files, named after the test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).expect("the scratch directory can be created");
+    dir
+}
+
+#[test]
+fn c_runs_a_command_string() {
+    assert_eq!(status(&pearsh(&["-c", "exit 3"])), 3);
+    assert_eq!(status(&pearsh(&["-c", "true"])), 0);
+    assert_eq!(status(&pearsh(&["-c", "false"])), 1);
+}
+
+#[test]
+fn c_runs_every_command_in_the_string() {
+    assert_eq!(status(&pearsh(&["-c", "true; exit 5"])), 5);
+    assert_eq!(status(&pearsh(&["-c", "true\nexit 5"])), 5);
+    assert_eq!(status(&pearsh(&["-c", "false || exit 6"])), 6);
+    assert_eq!(status(&pearsh(&["-c", "false && exit 6"])), 1);
+    assert_eq!(status(&pearsh(&["-c", "false && exit 7 || exit 8"])), 8);
+}
+
+#[test]
+fn c_without_a_command_is_an_error() {
+    let output = pearsh(&["-c"]);
+    assert_eq!(status(&output), 2);
+    assert!(stderr(&output).contains("option requires an argument"));
+}
+
+#[test]
+fn c_sets_the_name_and_positional_parameters() {
+    let output = pearsh(&["-c", "to-json 0; to-json 1; to-json 2; to-json '#'", "name", "a", "b"]);
+    assert_eq!(status(&output), 0);
+    assert_eq!(stdout(&output), "\"name\"\n\"a\"\n\"b\"\n\"2\"\n");
+}
+
+#[test]
+fn c_names_the_shell_pearsh_without_a_name() {
+    let output = pearsh(&["-c", "to-json 0; to-json '#'"]);
+    assert_eq!(stdout(&output), "\"pearsh\"\n\"0\"\n");
+}
+
+#[test]
+fn e_exits_when_a_command_fails() {
+    assert_eq!(status(&pearsh(&["-c", "false; exit 0"])), 0);
+    assert_eq!(status(&pearsh(&["-e", "-c", "false; exit 0"])), 1);
+}
+
+#[test]
+fn e_ignores_failures_that_are_tested() {
+    assert_eq!(status(&pearsh(&["-e", "-c", "false && true; exit 4"])), 4);
+    assert_eq!(status(&pearsh(&["-e", "-c", "false || true; exit 4"])), 4);
+    assert_eq!(status(&pearsh(&["-e", "-c", "true && false; exit 4"])), 1);
+}
+
+#[test]
+fn set_e_turns_errexit_on_and_off() {
+    assert_eq!(status(&pearsh(&["-c", "set -e; false; exit 0"])), 1);
+    assert_eq!(status(&pearsh(&["-e", "-c", "set +e; false; exit 0"])), 0);
+    assert_eq!(status(&pearsh(&["-c", "set -o strict; false; exit 0"])), 1);
+    assert_eq!(status(&pearsh(&["-c", "set -o strict; set +o errexit; false; exit 0"])), 0);
+    let options = stdout(&pearsh(&["-c", "set -e; set -o"]));
+    assert_eq!(options.lines().find(|line| line.starts_with("strict")), Some("strict         on"));
+}
+
+#[test]
+fn set_rejects_unknown_flags() {
+    assert_eq!(status(&pearsh(&["-c", "set -q"])), 2);
+    assert_eq!(status(&pearsh(&["-c", "set -"])), 2);
+    assert_eq!(status(&pearsh(&["-c", "set"])), 2);
+}
+
+#[test]
+fn x_traces_each_command() {
+    let output = pearsh(&["-x", "-c", "true; false"]);
+    assert_eq!(stderr(&output), "+ true\n+ false\n");
+}
+
+#[test]
+fn set_takes_the_same_flags_as_the_command_line() {
+    let output = pearsh(&["-c", "true; set -x; true; set +x; true"]);
+    assert_eq!(stderr(&output), "+ true\n+ set +x\n");
+
+    let output = pearsh(&["-c", "set -ex; false; exit 0"]);
+    assert_eq!(status(&output), 1);
+    assert_eq!(stderr(&output), "+ false\n");
+
+    let output = pearsh(&["-c", "set -e -x; set +xe; false; exit 0"]);
+    assert_eq!(status(&output), 0);
+    assert_eq!(stderr(&output), "+ set +xe\n");
+}
+
+#[test]
+fn flags_combine() {
+    assert_eq!(status(&pearsh(&["-ec", "false; exit 0"])), 1);
+
+    let output = pearsh(&["-xec", "true; false; exit 0"]);
+    assert_eq!(status(&output), 1);
+    assert_eq!(stderr(&output), "+ true\n+ false\n");
+}
+
+#[test]
+fn double_dash_ends_the_options() {
+    let dir = scratch("double_dash_ends_the_options");
+    fs::write(dir.join("-script"), "exit 4\n").unwrap();
+
+    assert_eq!(status(&pearsh_in(Some(&dir), &["--", "-script"])), 4);
+    let output = pearsh_in(Some(&dir), &["-script"]);
+    assert_eq!(status(&output), 2);
+    assert!(stderr(&output).contains("invalid option"));
+}
+
+#[test]
+fn scripts_get_their_path_and_arguments() {
+    let dir = scratch("scripts_get_their_path_and_arguments");
+    fs::write(dir.join("script.psh"), "to-json 0\nto-json 1\nto-json '#'\n").unwrap();
+
+    let output = pearsh_in(Some(&dir), &["script.psh", "arg"]);
+    assert_eq!(status(&output), 0);
+    assert_eq!(stdout(&output), "\"script.psh\"\n\"arg\"\n\"1\"\n");
+}
+
+#[test]
+fn sourced_files_get_their_own_arguments_for_a_while() {
+    let dir = scratch("sourced_files_get_their_own_arguments_for_a_while");
+    fs::write(dir.join("lib.psh"), "to-json 0\nto-json 1\nto-json '#'\n").unwrap();
+
+    let output = pearsh_in(Some(&dir), &["-c", "source lib.psh 'x y' z; to-json 1; to-json '#'", "name", "a"]);
+    assert_eq!(status(&output), 0);
+    assert_eq!(stdout(&output), "\"name\"\n\"x y\"\n\"2\"\n\"a\"\n\"1\"\n");
+
+    let output = pearsh_in(Some(&dir), &["-c", ". lib.psh", "name", "a"]);
+    assert_eq!(stdout(&output), "\"name\"\n\"a\"\n\"1\"\n");
+}
+
+#[test]
+fn invalid_options_exit_2() {
+    let output = pearsh(&["-q"]);
+    assert_eq!(status(&output), 2);
+    assert!(stderr(&output).contains("usage: pearsh"));
+}
+
+#[test]
+fn bad_exit_arguments_exit_2() {
+    assert_eq!(status(&pearsh(&["-c", "exit nope"])), 2);
+}
+
+#[test]
+fn unterminated_strings_exit_2() {
+    let output = pearsh(&["-c", "x='open"]);
+    assert_eq!(status(&output), 2);
+    assert!(stderr(&output).contains("E001"));
+}
+
+#[test]
+fn compound_commands_exit_2_before_anything_runs() {
+    let output = pearsh(&["-c", "exit 5\nif false; then\n  exit 3\nfi"]);
+    assert_eq!(status(&output), 2);
+    assert!(stderr(&output).contains("E009"));
+}
+
+#[test]
+fn pipelines_exit_2_before_anything_runs() {
+    let output = pearsh(&["-c", "exit 5; true | true"]);
+    assert_eq!(status(&output), 2);
+    assert!(stderr(&output).contains("E009"));
+}
+
+#[test]
+fn redirections_exit_2_before_anything_runs() {
+    let dir = scratch("redirections_exit_2_before_anything_runs");
+    for command in ["exit 5; true > out", "exit 5; true >> out", "exit 5; true < out", "exit 5; true 2>&1"] {
+        let output = pearsh_in(Some(&dir), &["-c", command]);
+        assert_eq!(status(&output), 2, "{:?}", command);
+        assert!(stderr(&output).contains("E009"), "{:?}", command);
+    }
+    assert!(!dir.join("out").exists());
+}
+
+#[test]
+fn commands_that_cant_run_fail_rather_than_succeed() {
+    let output = pearsh(&["-c", "[ -e /nonexistent ] && exit 7; exit 0"]);
+    assert_eq!(status(&output), 0);
+    assert!(stderr(&output).contains("E009"));
+
+    for command in ["[ -e /nonexistent ]", "'nope' arg", "A=1 cmd", "x=(1 2)", "int x = 5; $x", "$unset"] {
+        let output = pearsh(&["-c", command]);
+        assert_eq!(status(&output), 2, "{:?}", command);
+        assert!(stderr(&output).contains("E009"), "{:?}", command);
+    }
+    assert_eq!(status(&pearsh(&["-c", "bool b = true; $b"])), 0);
+}
+
+#[test]
+fn programs_on_path_are_not_run() {
+    let dir = scratch("programs_on_path_are_not_run");
+    fs::write(dir.join("marker"), "").unwrap();
+
+    // `rm` is on `$PATH` wherever the tests run, and would remove the file
+    // if it were run.
+    let output = pearsh_in(Some(&dir), &["-c", "rm marker"]);
+    assert_eq!(status(&output), 2);
+    assert!(stderr(&output).contains("can't execute external commands yet"));
+    assert!(dir.join("marker").exists());
+}
+
+#[test]
+fn commands_that_cant_be_run_exit_126() {
+    let dir = scratch("commands_that_cant_be_run_exit_126");
+    fs::write(dir.join("data"), "").unwrap();
+
+    let output = pearsh_in(Some(&dir), &["-c", "./data"]);
+    assert_eq!(status(&output), 126);
+    assert!(stderr(&output).contains("E003"));
+    assert_eq!(status(&pearsh_in(Some(&dir), &["."])), 126);
+}
+
+#[test]
+fn commands_that_arent_found_exit_127() {
+    let output = pearsh(&["-c", "no-such-command-anywhere"]);
+    assert_eq!(status(&output), 127);
+    assert!(stderr(&output).contains("E002"));
+    assert_eq!(status(&pearsh(&["/no/such/script.psh"])), 127);
+}